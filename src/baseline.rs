@@ -0,0 +1,116 @@
+use languagetool_rust::check::Match;
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::HashMap,
+	error::Error,
+	fs,
+	path::{Path, PathBuf},
+};
+
+use crate::convert::Chunk;
+
+/// Chars of the checked text before and after a match in its fingerprint
+const CONTEXT: usize = 20;
+
+/// Known matches, identified by file, rule and surrounding text to survive moved lines
+pub struct Baseline {
+	root: PathBuf,
+	counts: HashMap<Entry, usize>,
+	entries: Vec<Entry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+struct Entry {
+	/// Relative to the checked folder
+	file: String,
+	rule: String,
+	context: String,
+}
+
+impl Baseline {
+	pub fn new(root: &Path) -> Self {
+		Self {
+			root: root.to_owned(),
+			counts: HashMap::new(),
+			entries: Vec::new(),
+		}
+	}
+
+	pub fn load(path: &Path, root: &Path) -> Result<Self, Box<dyn Error>> {
+		let text = fs::read_to_string(path).map_err(|err| {
+			format!(
+				"Failed to read baseline {}, create it with `--write-baseline`: {}",
+				path.display(),
+				err
+			)
+		})?;
+		let entries: Vec<Entry> = serde_json::from_str(&text)
+			.map_err(|err| format!("Invalid baseline {}: {}", path.display(), err))?;
+		let mut baseline = Self::new(root);
+		for entry in entries {
+			*baseline.counts.entry(entry).or_default() += 1;
+		}
+		Ok(baseline)
+	}
+
+	/// Remove known matches, every entry is used once
+	pub fn filter(&mut self, file: &Path, chunk: &Chunk, matches: &mut Vec<Match>) {
+		if self.counts.is_empty() {
+			return;
+		}
+		let file = self.name(file);
+		matches.retain(|info| {
+			let entry = entry(&file, chunk, info);
+			match self.counts.get_mut(&entry) {
+				Some(count) if *count > 0 => {
+					*count -= 1;
+					false
+				},
+				_ => true,
+			}
+		});
+	}
+
+	pub fn add(&mut self, file: &Path, chunk: &Chunk, matches: &[Match]) {
+		let file = self.name(file);
+		self.entries
+			.extend(matches.iter().map(|info| entry(&file, chunk, info)));
+	}
+
+	pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+		fs::write(path, serde_json::to_string_pretty(&self.entries)?)?;
+		eprintln!(
+			"Wrote {} matches to baseline {}",
+			self.entries.len(),
+			path.display()
+		);
+		Ok(())
+	}
+
+	fn name(&self, file: &Path) -> String {
+		let file = file.strip_prefix(&self.root).unwrap_or(file);
+		file.to_string_lossy().replace('\\', "/")
+	}
+}
+
+fn entry(file: &str, chunk: &Chunk, info: &Match) -> Entry {
+	Entry {
+		file: file.to_owned(),
+		rule: info.rule.id.clone(),
+		context: context(chunk, info),
+	}
+}
+
+/// The match and the text around it in its chunk, unlike the context of the server this does not
+/// depend on the neighbouring chunks of the request
+fn context(chunk: &Chunk, info: &Match) -> String {
+	let start = info.offset.saturating_sub(CONTEXT);
+	chunk
+		.annotations
+		.iter()
+		.filter_map(|annotation| annotation.text.as_deref().or(annotation.markup.as_deref()))
+		.flat_map(str::chars)
+		.skip(start)
+		.take(info.offset - start + info.length + CONTEXT)
+		.collect()
+}
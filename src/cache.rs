@@ -0,0 +1,213 @@
+use languagetool_rust::check::{DataAnnotation, Match};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+	collections::{HashMap, HashSet},
+	env,
+	error::Error,
+	fs,
+	path::PathBuf,
+	time::{Duration, SystemTime},
+};
+
+use crate::{client::Client, Args};
+
+/// How long the stored server version is trusted
+const VERSION_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+/// Matches of already checked paragraphs, so only new or changed paragraphs are sent.
+/// Unless disabled, the matches are stored on disk and reused between runs.
+pub struct Cache {
+	entries: HashMap<String, Vec<Match>>,
+	used: HashSet<String>,
+	disk: Option<Disk>,
+	/// Settings changing the results, known after `prepare`
+	settings: Option<String>,
+}
+
+struct Disk {
+	dir: PathBuf,
+	/// In bytes
+	max_size: u64,
+	written: bool,
+}
+
+impl Cache {
+	pub fn new(args: &Args) -> Self {
+		let dir = if args.no_cache {
+			None
+		} else {
+			args.cache_dir.clone().or_else(default_dir)
+		};
+		Self {
+			entries: HashMap::new(),
+			used: HashSet::new(),
+			disk: dir.map(|dir| Disk {
+				dir,
+				max_size: args.cache_size.saturating_mul(1_000_000),
+				written: false,
+			}),
+			settings: None,
+		}
+	}
+
+	/// Collect the settings for the keys, the server version is only requested for the disk cache
+	pub async fn prepare(&mut self, client: &Client, args: &Args) -> Result<(), Box<dyn Error>> {
+		if self.settings.is_some() {
+			return Ok(());
+		}
+		let version = match &self.disk {
+			Some(disk) => disk.version(client).await?,
+			None => String::new(),
+		};
+		// servers of the same version can have other data, premium accounts get more matches
+		let settings = json!([
+			client.api(),
+			version,
+			args.username,
+			args.api_key.is_some(),
+			args.enabled_rules,
+			args.disabled_rules,
+			args.enabled_categories,
+			args.disabled_categories,
+			args.enabled_only,
+		]);
+		self.settings = Some(settings.to_string());
+		Ok(())
+	}
+
+	pub fn key(&self, language: &str, annotations: &[DataAnnotation]) -> String {
+		let mut hasher = Sha256::new();
+		hasher.update(self.settings.as_deref().unwrap_or_default());
+		hasher.update([0]);
+		hasher.update(language);
+		hasher.update([0]);
+		hasher.update(serde_json::to_vec(annotations).unwrap());
+		format!("{:x}", hasher.finalize())
+	}
+
+	pub fn get(&mut self, key: &str) -> Option<Vec<Match>> {
+		self.used.insert(key.to_owned());
+		if let Some(matches) = self.entries.get(key) {
+			return Some(matches.clone());
+		}
+		let disk = self.disk.as_ref()?;
+		let content = fs::read(disk.path(key)).ok()?;
+		let matches = serde_json::from_slice::<Vec<Match>>(&content).ok()?;
+		self.entries.insert(key.to_owned(), matches.clone());
+		Some(matches)
+	}
+
+	pub fn insert(&mut self, key: String, matches: Vec<Match>) {
+		if let Some(disk) = &mut self.disk {
+			disk.write(&key, &matches);
+		}
+		self.used.insert(key.clone());
+		self.entries.insert(key, matches);
+	}
+
+	/// Forget paragraphs not used since the last call and limit the size on disk
+	pub fn prune(&mut self) {
+		let used = std::mem::take(&mut self.used);
+		self.entries.retain(|key, _| used.contains(key));
+		if let Some(disk) = &mut self.disk {
+			disk.trim();
+		}
+	}
+}
+
+impl Disk {
+	fn path(&self, key: &str) -> PathBuf {
+		self.dir.join(format!("{}.json", key))
+	}
+
+	/// Server version, stored per server for `VERSION_LIFETIME` to save the request between runs
+	async fn version(&self, client: &Client) -> Result<String, Box<dyn Error>> {
+		let path = self
+			.dir
+			.join(format!("{:x}.version", Sha256::digest(client.api())));
+		let fresh = fs::metadata(&path)
+			.and_then(|metadata| metadata.modified())
+			.ok()
+			.and_then(|modified| modified.elapsed().ok())
+			.is_some_and(|age| age < VERSION_LIFETIME);
+		if fresh {
+			if let Ok(version) = fs::read_to_string(&path) {
+				return Ok(version);
+			}
+		}
+		let version = client.version().await?;
+		if fs::create_dir_all(&self.dir).is_ok() {
+			let _ = fs::write(&path, &version);
+		}
+		Ok(version)
+	}
+
+	// the cache is optional, failing to write is not an error
+	fn write(&mut self, key: &str, matches: &[Match]) {
+		let content = match serde_json::to_vec(matches) {
+			Ok(content) => content,
+			Err(_) => return,
+		};
+		if fs::create_dir_all(&self.dir).is_ok() && fs::write(self.path(key), content).is_ok() {
+			self.written = true;
+		}
+	}
+
+	/// Remove the oldest entries until the cache fits into `max_size`
+	fn trim(&mut self) {
+		if !std::mem::take(&mut self.written) {
+			return;
+		}
+		let entries = match fs::read_dir(&self.dir) {
+			Ok(entries) => entries,
+			Err(_) => return,
+		};
+		let mut files = entries
+			.filter_map(Result::ok)
+			.filter_map(|entry| {
+				let metadata = entry.metadata().ok()?;
+				let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+				Some((modified, metadata.len(), entry.path()))
+			})
+			.collect::<Vec<_>>();
+		let mut size = files.iter().map(|(_, len, _)| len).sum::<u64>();
+		files.sort();
+		for (_, len, path) in files {
+			if size <= self.max_size {
+				break;
+			}
+			if fs::remove_file(path).is_ok() {
+				size -= len;
+			}
+		}
+	}
+}
+
+/// Cache folder of the user, `$XDG_CACHE_HOME`, `~/.cache` or `%LOCALAPPDATA%`
+fn default_dir() -> Option<PathBuf> {
+	let base = env::var_os("XDG_CACHE_HOME")
+		.map(PathBuf::from)
+		.or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+		.or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+	Some(base.join("typst-lt"))
+}
+
+pub fn clear(args: &Args) -> Result<(), Box<dyn Error>> {
+	let dir = match args.cache_dir.clone().or_else(default_dir) {
+		Some(dir) => dir,
+		None => return Err("No cache folder found".into()),
+	};
+	let mut removed = 0;
+	if dir.is_dir() {
+		for entry in fs::read_dir(&dir)? {
+			let path = entry?.path();
+			if matches!(path.extension(), Some(ext) if ext == "json" || ext == "version") {
+				fs::remove_file(path)?;
+				removed += 1;
+			}
+		}
+	}
+	println!("Removed {} entries from {}", removed, dir.display());
+	Ok(())
+}
@@ -0,0 +1,219 @@
+use languagetool_rust::{check::CheckRequest, languages::LanguagesResponse, CheckResponse};
+use reqwest::{header::RETRY_AFTER, Response, StatusCode};
+use std::{collections::VecDeque, time::Duration};
+use tokio::{
+	sync::{Mutex, OnceCell},
+	time::{sleep, sleep_until, Instant},
+};
+
+use crate::Args;
+
+/// Error messages are kept as strings, so requests can be sent from other tasks
+pub type Result<T> = std::result::Result<T, String>;
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// Limits of the official API without premium access
+const FREE_REQUESTS_PER_MINUTE: usize = 20;
+const FREE_CHARS_PER_MINUTE: usize = 75_000;
+/// Longest delay between retries is `2^MAX_BACKOFF` seconds
+const MAX_BACKOFF: u32 = 6;
+const MAX_DELAY: Duration = Duration::from_secs(1 << MAX_BACKOFF);
+
+/// Connection to the LanguageTool server with rate limit and retries
+pub struct Client {
+	http: reqwest::Client,
+	api: String,
+	username: Option<String>,
+	api_key: Option<String>,
+	retries: u32,
+	limit: Option<Mutex<RateLimit>>,
+	/// Requested once and shared by all caches
+	version: OnceCell<String>,
+}
+
+impl Client {
+	pub fn new(args: &Args) -> Self {
+		let api = if args.port.is_empty() {
+			format!("{}/v2", args.host)
+		} else {
+			format!("{}:{}/v2", args.host, args.port)
+		};
+		let (requests, chars) = match (args.use_official_api, &args.api_key) {
+			(true, None) => (
+				args.requests_per_minute.or(Some(FREE_REQUESTS_PER_MINUTE)),
+				args.chars_per_minute.or(Some(FREE_CHARS_PER_MINUTE)),
+			),
+			_ => (args.requests_per_minute, args.chars_per_minute),
+		};
+		let limit = match (requests, chars) {
+			(None, None) => None,
+			(requests, chars) => Some(Mutex::new(RateLimit {
+				requests: requests.unwrap_or(usize::MAX),
+				chars: chars.unwrap_or(usize::MAX),
+				sent: VecDeque::new(),
+			})),
+		};
+		Self {
+			http: reqwest::Client::new(),
+			api,
+			username: args.username.clone(),
+			api_key: args.api_key.clone(),
+			retries: args.retries,
+			limit,
+			version: OnceCell::new(),
+		}
+	}
+
+	/// URL of the API, without a trailing slash
+	pub fn api(&self) -> &str {
+		&self.api
+	}
+
+	/// Version of the server, from checking a short text
+	pub async fn version(&self) -> Result<String> {
+		self.version
+			.get_or_try_init(|| async {
+				let request = CheckRequest::default()
+					.with_language(String::from("en-US"))
+					.with_text(String::from("a"));
+				Ok(self.check(request).await?.software.version)
+			})
+			.await
+			.cloned()
+	}
+
+	pub async fn check(&self, mut request: CheckRequest) -> Result<CheckResponse> {
+		request.username = self.username.clone();
+		request.api_key = self.api_key.clone();
+		let size = size(&request);
+
+		let mut attempt = 0;
+		loop {
+			// retries count against the limit as well
+			if let Some(limit) = &self.limit {
+				limit.lock().await.acquire(size).await;
+			}
+			let result = self
+				.http
+				.post(format!("{}/check", self.api))
+				.form(&request)
+				.send()
+				.await;
+			let delay = Duration::from_secs(1 << attempt.min(MAX_BACKOFF));
+			match result {
+				Ok(response) if response.status().is_success() => {
+					return response.json().await.map_err(|err| err.to_string())
+				},
+				Ok(response) if retry(response.status()) && attempt < self.retries => {
+					sleep(retry_after(&response).unwrap_or(delay).min(MAX_DELAY)).await;
+				},
+				Ok(response) => return Err(self.describe(response).await),
+				// the server might be restarting
+				Err(err) if err.is_connect() && attempt < self.retries => sleep(delay).await,
+				Err(err) => return Err(self.unreachable(err)),
+			}
+			attempt += 1;
+		}
+	}
+
+	/// Languages supported by the server
+	pub async fn languages(&self) -> Result<LanguagesResponse> {
+		let response = self
+			.http
+			.get(format!("{}/languages", self.api))
+			.send()
+			.await
+			.map_err(|err| self.unreachable(err))?;
+		if !response.status().is_success() {
+			return Err(self.describe(response).await);
+		}
+		response.json().await.map_err(|err| err.to_string())
+	}
+
+	fn unreachable(&self, err: reqwest::Error) -> String {
+		if err.is_connect() {
+			format!(
+				"Could not connect to the LanguageTool server at {}, is it running? ({})",
+				self.api, err
+			)
+		} else {
+			err.to_string()
+		}
+	}
+
+	async fn describe(&self, response: Response) -> String {
+		let status = response.status();
+		let body = response.text().await.unwrap_or_default();
+		match status {
+			StatusCode::TOO_MANY_REQUESTS => format!(
+				"Rate limit of the LanguageTool server exceeded, try a lower `--requests-per-minute` or `--chars-per-minute`: {}",
+				body
+			),
+			StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+				format!("Access denied, check `--username` and `--api-key`: {}", body)
+			},
+			_ => format!("LanguageTool server responded with {}: {}", status, body),
+		}
+	}
+}
+
+fn retry(status: StatusCode) -> bool {
+	status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+	let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+	value.parse().ok().map(Duration::from_secs)
+}
+
+/// Characters of the checked text and markup
+fn size(request: &CheckRequest) -> usize {
+	let text = request.text.as_ref().map_or(0, |text| text.chars().count());
+	let data = request
+		.data
+		.iter()
+		.flat_map(|data| &data.annotation)
+		.map(|annotation| {
+			annotation
+				.text
+				.as_ref()
+				.map_or(0, |text| text.chars().count())
+				+ annotation
+					.markup
+					.as_ref()
+					.map_or(0, |markup| markup.chars().count())
+		})
+		.sum::<usize>();
+	text + data
+}
+
+/// Requests sent in the last minute
+struct RateLimit {
+	requests: usize,
+	chars: usize,
+	sent: VecDeque<(Instant, usize)>,
+}
+
+impl RateLimit {
+	async fn acquire(&mut self, chars: usize) {
+		loop {
+			let now = Instant::now();
+			while let Some(&(time, _)) = self.sent.front() {
+				if now.duration_since(time) < MINUTE {
+					break;
+				}
+				self.sent.pop_front();
+			}
+			let used = self.sent.iter().map(|&(_, chars)| chars).sum::<usize>();
+			if self.sent.is_empty()
+				|| (self.sent.len() < self.requests && used + chars <= self.chars)
+			{
+				self.sent.push_back((now, chars));
+				return;
+			}
+			let (oldest, _) = self.sent[0];
+			sleep_until(oldest + MINUTE).await;
+		}
+	}
+}
@@ -0,0 +1,151 @@
+use clap::{parser::ValueSource, ArgMatches};
+use serde::Deserialize;
+use std::{
+	collections::HashMap,
+	error::Error,
+	fs,
+	path::{Path, PathBuf},
+};
+
+use crate::{
+	rules::{Code, Function, Math},
+	severity::{FailOn, Severity},
+	Args,
+};
+
+pub const FILE_NAME: &str = "typst-lt.toml";
+
+/// Project settings from `typst-lt.toml`, flags given on the command line take precedence
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+	pub language: Option<String>,
+	pub host: Option<String>,
+	pub port: Option<String>,
+	/// Relative to the config file
+	pub server_jar: Option<PathBuf>,
+	pub max_request_length: Option<usize>,
+	pub concurrency: Option<usize>,
+	pub requests_per_minute: Option<usize>,
+	pub chars_per_minute: Option<usize>,
+	pub retries: Option<u32>,
+	pub enabled_rules: Vec<String>,
+	pub disabled_rules: Vec<String>,
+	pub enabled_categories: Vec<String>,
+	pub disabled_categories: Vec<String>,
+	pub enabled_only: bool,
+	/// Severity per category
+	pub severity: HashMap<String, Severity>,
+	pub fail_on: Option<FailOn>,
+	pub max_warnings: Option<usize>,
+	/// Relative to the config file
+	pub baseline: Option<PathBuf>,
+	/// Relative to the config file
+	pub dictionaries: Vec<PathBuf>,
+	pub functions: HashMap<String, Function>,
+	pub code: Option<Code>,
+	pub math: Option<Math>,
+}
+
+impl Config {
+	/// First config file in `path` or one of its parent folders
+	pub fn find(path: &Path) -> Option<PathBuf> {
+		let path = fs::canonicalize(path).ok()?;
+		path.ancestors()
+			.map(|dir| dir.join(FILE_NAME))
+			.find(|file| file.is_file())
+	}
+
+	pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+		let text = fs::read_to_string(path)?;
+		let mut config: Self = toml::from_str(&text)
+			.map_err(|err| format!("Invalid config file {}: {}", path.display(), err))?;
+		let dir = path.parent().unwrap_or(Path::new("."));
+		if let Some(jar) = &mut config.server_jar {
+			*jar = dir.join(&*jar);
+		}
+		if let Some(baseline) = &mut config.baseline {
+			*baseline = dir.join(&*baseline);
+		}
+		for dictionary in &mut config.dictionaries {
+			*dictionary = dir.join(&*dictionary);
+		}
+		Ok(config)
+	}
+
+	pub fn apply(self, args: &mut Args, matches: &ArgMatches) {
+		if args.language.is_none() {
+			args.language = self.language;
+		}
+		if let Some(host) = self.host.filter(|_| is_default(matches, "host")) {
+			args.host = host;
+		}
+		if let Some(port) = self.port.filter(|_| is_default(matches, "port")) {
+			args.port = port;
+		}
+		if args.server_jar.is_none() {
+			args.server_jar = self.server_jar;
+		}
+		if let Some(length) = self
+			.max_request_length
+			.filter(|_| is_default(matches, "max_request_length"))
+		{
+			args.max_request_length = length;
+		}
+		if let Some(concurrency) = self
+			.concurrency
+			.filter(|_| is_default(matches, "concurrency"))
+		{
+			args.concurrency = concurrency;
+		}
+		if args.requests_per_minute.is_none() {
+			args.requests_per_minute = self.requests_per_minute;
+		}
+		if args.chars_per_minute.is_none() {
+			args.chars_per_minute = self.chars_per_minute;
+		}
+		if let Some(retries) = self.retries.filter(|_| is_default(matches, "retries")) {
+			args.retries = retries;
+		}
+		if is_default(matches, "enabled_rules") {
+			args.enabled_rules = self.enabled_rules;
+		}
+		if is_default(matches, "disabled_rules") {
+			args.disabled_rules = self.disabled_rules;
+		}
+		if is_default(matches, "enabled_categories") {
+			args.enabled_categories = self.enabled_categories;
+		}
+		if is_default(matches, "disabled_categories") {
+			args.disabled_categories = self.disabled_categories;
+		}
+		if is_default(matches, "enabled_only") {
+			args.enabled_only = self.enabled_only;
+		}
+		let mut severities = self.severity.into_iter().collect::<Vec<_>>();
+		severities.append(&mut args.severities);
+		args.severities = severities;
+		if let Some(fail_on) = self.fail_on.filter(|_| is_default(matches, "fail_on")) {
+			args.fail_on = fail_on;
+		}
+		if let Some(max) = self
+			.max_warnings
+			.filter(|_| is_default(matches, "max_warnings"))
+		{
+			args.max_warnings = max;
+		}
+		if args.baseline.is_none() {
+			args.baseline = self.baseline;
+		}
+		if is_default(matches, "dictionaries") {
+			args.dictionaries = self.dictionaries;
+		}
+		args.functions = self.functions;
+		args.code = self.code;
+		args.math = self.math;
+	}
+}
+
+fn is_default(matches: &ArgMatches, id: &str) -> bool {
+	!matches!(matches.value_source(id), Some(ValueSource::CommandLine))
+}
@@ -1,19 +1,46 @@
 use languagetool_rust::check::DataAnnotation;
-use typst_syntax::{SyntaxKind, SyntaxNode};
+use typst_syntax::{ast, SyntaxKind, SyntaxNode};
 
-use crate::rules::Rules;
+use crate::{
+	rules::{Code, Function, Rules},
+	source_map::SourceMap,
+	suppression::Suppressions,
+};
 
-pub fn convert(
-	node: &SyntaxNode,
-	rules: &Rules,
-	max_length: usize,
-) -> Vec<(Vec<DataAnnotation>, usize)> {
-	let state = State { mode: Mode::Markdown };
+/// Paragraph of the document
+pub struct Chunk {
+	pub annotations: Vec<DataAnnotation>,
+	/// Length in chars of the source text
+	pub length: usize,
+	/// Language set with `text(lang: ..)`
+	pub language: Option<String>,
+	pub map: SourceMap,
+}
+
+impl Chunk {
+	fn new(language: Option<String>) -> Self {
+		Self {
+			annotations: Vec::new(),
+			length: 0,
+			language,
+			map: SourceMap::default(),
+		}
+	}
+}
+
+/// Split the document into paragraphs, which are checked independently.
+/// Separate content like footnotes follows the paragraph it is part of.
+pub fn convert(node: &SyntaxNode, rules: &Rules) -> (Vec<Chunk>, Suppressions) {
+	let state = State {
+		mode: Mode::Markdown,
+		strings: false,
+		inline: false,
+	};
 	let mut output = Output::new();
 	for child in node.children() {
 		state.convert(child, &mut output, rules);
 		if child.kind() == SyntaxKind::Parbreak {
-			output.maybe_seperate(max_length);
+			output.maybe_seperate();
 		}
 	}
 	output.result()
@@ -26,30 +53,62 @@ enum OutputState {
 }
 
 struct Output {
-	items: Vec<(Vec<DataAnnotation>, usize)>,
+	items: Vec<Chunk>,
 	state: OutputState,
+	/// Byte offset in the source
+	position: usize,
+	/// Char offset in the checked text of the current chunk
+	checked: usize,
+	suppressions: Suppressions,
+	language: Option<String>,
+	/// Separate content, added after the chunk it interrupted
+	notes: Vec<Chunk>,
 }
 
 impl Output {
 	pub fn new() -> Self {
 		Self {
-			items: vec![(Vec::new(), 0)],
+			items: vec![Chunk::new(None)],
 			state: OutputState::Text(String::new()),
+			position: 0,
+			checked: 0,
+			suppressions: Suppressions::new(),
+			language: None,
+			notes: Vec::new(),
 		}
 	}
 
+	fn advance(&mut self, source: &str, text: bool) {
+		let chunk = self.items.last_mut().unwrap();
+		chunk.map.push(self.checked, source, self.position, text);
+		for (index, c) in source.char_indices() {
+			if c == '\n' {
+				self.suppressions.newline(self.position + index);
+			}
+		}
+		self.checked += source.chars().count();
+		self.position += source.len();
+	}
+
+	pub fn add_comment(&mut self, text: String) {
+		self.add_markup(text.clone());
+		self.suppressions.comment(&text, self.position);
+	}
+
 	fn add_item(&mut self, item: DataAnnotation) {
+		let chunk = self.items.last_mut().unwrap();
 		if let Some(text) = &item.text {
-			self.items.last_mut().unwrap().1 += text.chars().count();
+			chunk.length += text.chars().count();
 		}
 		if let Some(text) = &item.markup {
-			self.items.last_mut().unwrap().1 += text.chars().count();
+			chunk.length += text.chars().count();
 		}
-		self.items.last_mut().unwrap().0.push(item);
+		chunk.annotations.push(item);
 	}
 
 	// is possible without cloning, but not naive in safe rust
 	pub fn add_text(&mut self, text: String) {
+		self.advance(&text, true);
 		self.state = match &self.state {
 			OutputState::Text(t) => OutputState::Text(t.clone() + &text),
 			OutputState::Markup(t) => {
@@ -64,6 +123,7 @@ impl Output {
 	}
 
 	pub fn add_markup(&mut self, text: String) {
+		self.advance(&text, false);
 		self.state = match &self.state {
 			OutputState::Text(t) => {
 				self.add_item(DataAnnotation::new_text(t.clone()));
@@ -77,6 +137,7 @@ impl Output {
 		}
 	}
 	pub fn add_encoded(&mut self, text: String, res: String) {
+		self.advance(&text, false);
 		self.state = match &self.state {
 			OutputState::Text(t) => {
 				self.add_item(DataAnnotation::new_text(t.clone()));
@@ -100,18 +161,89 @@ impl Output {
 		}
 	}
 
-	pub fn maybe_seperate(&mut self, max: usize) {
-		if self.items.last().unwrap().1 > max {
-			self.flush();
-			self.state = OutputState::Text(String::new());
-			self.items.push((Vec::new(), 0));
+	fn seperate(&mut self) {
+		self.flush();
+		self.items.append(&mut self.notes);
+		self.state = OutputState::Text(String::new());
+		self.items.push(Chunk::new(self.language.clone()));
+		self.checked = 0;
+	}
+
+	pub fn maybe_seperate(&mut self) {
+		if self.items.last().unwrap().length > 0 {
+			self.seperate();
+		}
+	}
+
+	pub fn set_language(&mut self, language: Option<String>) {
+		if language == self.language {
+			return;
+		}
+		self.language = language;
+		// a chunk with only markup so far, like `#set text(..)`, is checked in the new language
+		let pending = match &self.state {
+			OutputState::Text(t) => has_text(t),
+			_ => false,
+		};
+		let chunk = self.items.last_mut().unwrap();
+		let added = chunk
+			.annotations
+			.iter()
+			.any(|annotation| annotation.text.as_deref().is_some_and(has_text));
+		if pending || added {
+			self.seperate();
+		} else {
+			chunk.language = self.language.clone();
 		}
 	}
 
-	pub fn result(mut self) -> Vec<(Vec<DataAnnotation>, usize)> {
+	/// Interrupt the current chunk for content checked on its own
+	fn begin_separate(&mut self) -> Interrupted {
 		self.flush();
-		self.items
+		let chunk = self.items.pop().unwrap();
+		let start = self.items.len();
+		self.items.push(Chunk::new(self.language.clone()));
+		self.state = OutputState::Text(String::new());
+		Interrupted {
+			chunk,
+			checked: std::mem::replace(&mut self.checked, 0),
+			start,
+			notes: std::mem::take(&mut self.notes),
+		}
+	}
+
+	fn end_separate(&mut self, mut interrupted: Interrupted) {
+		self.flush();
+		let mut chunks = self.items.split_off(interrupted.start);
+		chunks.append(&mut self.notes);
+		interrupted.notes.append(&mut chunks);
+		self.notes = interrupted.notes;
+		self.items.push(interrupted.chunk);
+		self.state = OutputState::Text(String::new());
+		self.checked = interrupted.checked;
 	}
+
+	pub fn result(mut self) -> (Vec<Chunk>, Suppressions) {
+		self.flush();
+		self.items.append(&mut self.notes);
+		self.suppressions.finish();
+		self.items.retain(|chunk| chunk.length > 0);
+		(self.items, self.suppressions)
+	}
+}
+
+fn has_text(text: &str) -> bool {
+	!text.trim().is_empty()
+}
+
+/// Chunk continued after separate content
+struct Interrupted {
+	chunk: Chunk,
+	checked: usize,
+	/// Index of the first chunk of the separate content
+	start: usize,
+	/// Separate content of the interrupted chunk so far
+	notes: Vec<Chunk>,
 }
 
 #[derive(PartialEq, Clone, Copy)]
@@ -123,6 +255,10 @@ enum Mode {
 #[derive(Clone, Copy)]
 struct State {
 	mode: Mode,
+	/// Check string literals
+	strings: bool,
+	/// Brackets of content blocks are part of the surrounding text
+	inline: bool,
 }
 
 impl State {
@@ -130,33 +266,87 @@ impl State {
 		match node.kind() {
 			SyntaxKind::Text if self.mode == Mode::Markdown => output.add_text(node.text().into()),
 			SyntaxKind::Equation => {
-				output.add_encoded(node.text().into(), String::from("0"));
-				Self::skip(node, output);
+				let math = rules.math.clone().unwrap_or_default();
+				let display = node
+					.cast::<ast::Equation>()
+					.is_some_and(|equation| equation.block());
+				let placeholder = if display { math.display } else { math.inline };
+				output.add_encoded(node.text().into(), placeholder);
+				if math.strings {
+					Self::math(node, output);
+				} else {
+					Self::skip(node, output);
+				}
 			},
 			SyntaxKind::FuncCall => {
 				self.mode = Mode::Code;
+				self.strings = false;
 				let name = node.children().next().unwrap().text();
 				let rule = rules.functions.get(name.as_str());
+				self.inline = rule.is_some_and(|f| f.inline);
+				if let Some(f) = rule {
+					if f.skip {
+						Self::skip(node, output);
+						return;
+					}
+					if let Some(replacement) = f.replace_with.as_ref().filter(|_| !f.separate) {
+						output.add_encoded(String::new(), replacement.to_owned());
+						Self::skip(node, output);
+						return;
+					}
+				}
+				let interrupted = rule.filter(|f| f.separate).map(|_| output.begin_separate());
+				let language = output.language.clone();
+				if let Some(lang) = text_language(node) {
+					output.set_language(Some(lang));
+				}
 				if let Some(f) = rule {
 					output.add_encoded(String::new(), f.before.to_owned());
 				}
 				for child in node.children() {
-					self.convert(child, output, rules);
+					match rule {
+						Some(f) if child.kind() == SyntaxKind::Args && f.is_selective() => {
+							self.arguments(child, output, rules, f)
+						},
+						_ => self.convert(child, output, rules),
+					}
 				}
 				if let Some(f) = rule {
 					output.add_encoded(String::new(), f.after.to_owned());
 				}
+				output.set_language(language);
+				if let Some(interrupted) = interrupted {
+					output.end_separate(interrupted);
+					let placeholder = rule.and_then(|f| f.replace_with.clone());
+					output.add_encoded(String::new(), placeholder.unwrap_or_default());
+				}
 			},
-			SyntaxKind::Code
-			| SyntaxKind::ModuleImport
-			| SyntaxKind::ModuleInclude
-			| SyntaxKind::LetBinding
-			| SyntaxKind::ShowRule
-			| SyntaxKind::SetRule => {
+			SyntaxKind::Code | SyntaxKind::LetBinding => {
 				self.mode = Mode::Code;
+				self.strings = code(rules).strings;
+				self.inline = false;
+				let language = output.language.clone();
 				for child in node.children() {
 					self.convert(child, output, rules);
 				}
+				output.set_language(language);
+			},
+			SyntaxKind::ModuleImport | SyntaxKind::ModuleInclude | SyntaxKind::ShowRule => {
+				self.mode = Mode::Code;
+				self.strings = false;
+				for child in node.children() {
+					self.convert(child, output, rules);
+				}
+			},
+			SyntaxKind::SetRule => {
+				self.mode = Mode::Code;
+				self.strings = false;
+				for child in node.children() {
+					self.convert(child, output, rules);
+				}
+				if let Some(lang) = text_language(node) {
+					output.set_language(Some(lang));
+				}
 			},
 			SyntaxKind::Heading => {
 				output.add_encoded(String::new(), String::from("\n\n"));
@@ -169,19 +359,38 @@ impl State {
 				output.add_encoded(String::new(), String::from("X"));
 				Self::skip(node, output);
 			},
+			SyntaxKind::LeftBracket | SyntaxKind::RightBracket if self.inline => {
+				output.add_markup(node.text().into());
+			},
 			SyntaxKind::LeftBracket | SyntaxKind::RightBracket => {
 				output.add_encoded(node.text().into(), String::from("\n\n"));
 			},
+			SyntaxKind::ContentBlock if !code(rules).content => Self::skip(node, output),
+			SyntaxKind::ContentBlock => {
+				let language = output.language.clone();
+				for child in node.children() {
+					self.convert(child, output, rules);
+				}
+				output.set_language(language);
+			},
+			SyntaxKind::Str if self.strings => Self::string(node, output),
 			SyntaxKind::Markup => {
 				self.mode = Mode::Markdown;
+				self.strings = false;
+				self.inline = false;
+				let language = output.language.clone();
 				for child in node.children() {
 					self.convert(child, output, rules);
 				}
+				output.set_language(language);
 			},
 			SyntaxKind::Shorthand if node.text() == "~" => {
 				output.add_encoded(node.text().into(), String::from(" "));
 			},
 			SyntaxKind::Space if self.mode == Mode::Markdown => output.add_text(node.text().into()),
+			SyntaxKind::LineComment | SyntaxKind::BlockComment => {
+				output.add_comment(node.text().into())
+			},
 			SyntaxKind::Parbreak => output.add_encoded(node.text().into(), String::from("\n\n")),
 			SyntaxKind::SmartQuote if self.mode == Mode::Markdown => {
 				output.add_text(node.text().into())
@@ -195,6 +404,67 @@ impl State {
 		}
 	}
 
+	/// Only the arguments selected by `function` are checked
+	fn arguments(self, node: &SyntaxNode, output: &mut Output, rules: &Rules, function: &Function) {
+		for child in node.children() {
+			match child.kind() {
+				SyntaxKind::ContentBlock if function.check_content_only => {
+					self.content(child, output, rules)
+				},
+				// nested calls are checked by their own rules
+				SyntaxKind::FuncCall if function.check_content_only => {
+					self.convert(child, output, rules)
+				},
+				SyntaxKind::Named if function.check_args.iter().any(|arg| arg == name(child)) => {
+					for part in child.children() {
+						match part.kind() {
+							SyntaxKind::Str => Self::string(part, output),
+							SyntaxKind::ContentBlock => self.content(part, output, rules),
+							SyntaxKind::FuncCall => self.convert(part, output, rules),
+							_ => Self::skip(part, output),
+						}
+					}
+				},
+				_ => Self::skip(child, output),
+			}
+		}
+	}
+
+	/// Content block selected by a function rule, checked even if content in code is not
+	fn content(self, node: &SyntaxNode, output: &mut Output, rules: &Rules) {
+		for child in node.children() {
+			self.convert(child, output, rules);
+		}
+	}
+
+	/// Check the content of a string literal, the quotes separate it like brackets
+	fn string(node: &SyntaxNode, output: &mut Output) {
+		let text = node.text();
+		match text
+			.strip_prefix('"')
+			.and_then(|text| text.strip_suffix('"'))
+		{
+			Some(content) => {
+				output.add_encoded(String::from("\""), String::from("\n\n"));
+				output.add_text(content.into());
+				output.add_encoded(String::from("\""), String::from("\n\n"));
+			},
+			None => output.add_markup(text.into()),
+		}
+	}
+
+	/// Only string literals in equations are checked
+	fn math(node: &SyntaxNode, output: &mut Output) {
+		if node.kind() == SyntaxKind::Str {
+			Self::string(node, output);
+			return;
+		}
+		output.add_markup(node.text().into());
+		for child in node.children() {
+			Self::math(child, output);
+		}
+	}
+
 	fn skip(node: &SyntaxNode, output: &mut Output) {
 		output.add_markup(node.text().into());
 		for child in node.children() {
@@ -202,3 +472,125 @@ impl State {
 		}
 	}
 }
+
+fn code(rules: &Rules) -> Code {
+	rules.code.unwrap_or_default()
+}
+
+/// Name of a named argument
+fn name(node: &SyntaxNode) -> &str {
+	node.children()
+		.next()
+		.map_or("", |ident| ident.text().as_str())
+}
+
+/// Language of `text(lang: .., region: ..)` or `set text(lang: .., region: ..)`
+fn text_language(node: &SyntaxNode) -> Option<String> {
+	let target = node
+		.children()
+		.find(|child| child.kind() == SyntaxKind::Ident)?;
+	if target.text() != "text" {
+		return None;
+	}
+	let args = node
+		.children()
+		.find(|child| child.kind() == SyntaxKind::Args)?;
+	let argument = |name: &str| {
+		args.children()
+			.filter(|child| child.kind() == SyntaxKind::Named)
+			.find(|named| named.children().next().map(|ident| ident.text().as_str()) == Some(name))
+			.and_then(|named| named.children().last())
+			.filter(|value| value.kind() == SyntaxKind::Str)
+			.map(|value| value.text().trim_matches('"').to_owned())
+	};
+	let lang = argument("lang")?;
+	match argument("region") {
+		Some(region) => Some(format!("{}-{}", lang, region.to_uppercase())),
+		None => Some(lang),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use languagetool_rust::check::Match;
+
+	use super::*;
+	use crate::output::Lines;
+
+	const FOOTNOTE: &str = "Hello#footnote[Note.] world.\n\nNext.";
+
+	fn chunks(source: &str) -> Vec<Chunk> {
+		convert(&typst_syntax::parse(source), &Rules::builtin()).0
+	}
+
+	/// Text as checked by the server
+	fn checked(chunk: &Chunk) -> String {
+		chunk
+			.annotations
+			.iter()
+			.filter_map(|annotation| {
+				annotation
+					.text
+					.as_deref()
+					.or(annotation.interpret_as.as_deref())
+			})
+			.collect()
+	}
+
+	#[test]
+	fn nested_calls() {
+		let chunks = chunks("#figure(table([a b]), caption: [c])\n");
+		assert_eq!(chunks.len(), 1);
+		assert_eq!(checked(&chunks[0]), "\n\na b\n\n\n\nc\n\n\n");
+	}
+
+	#[test]
+	fn footnote_order() {
+		let chunks = chunks(FOOTNOTE);
+		let texts = chunks
+			.iter()
+			.map(|chunk| checked(chunk).trim().to_owned())
+			.collect::<Vec<_>>();
+		assert_eq!(texts, ["Hello world.", "Note.", "Next."]);
+	}
+
+	#[test]
+	fn after_footnote() {
+		let chunks = chunks(FOOTNOTE);
+		// "world" at char 7 of the chunk, after "Hello", the "#" of the call and a space
+		let info = serde_json::from_value::<Match>(serde_json::json!({
+			"context": { "text": "Hello world.", "offset": 6, "length": 5 },
+			"contextForSureMatch": 0,
+			"ignoreForIncompleteSentence": false,
+			"length": 5,
+			"message": "",
+			"offset": 7,
+			"replacements": [],
+			"rule": {
+				"id": "RULE",
+				"description": "",
+				"issueType": "misspelling",
+				"category": { "id": "TYPOS", "name": "" }
+			},
+			"sentence": "Hello world.",
+			"shortMessage": "",
+			"type": { "typeName": "Other" }
+		}))
+		.unwrap();
+		let (start, end) = Lines::new(FOOTNOTE).locate(&chunks[0], &info);
+		assert_eq!(&FOOTNOTE[start.offset..end.offset], "world");
+		assert_eq!((start.line, start.column), (1, 23));
+	}
+
+	#[test]
+	fn language_of_first_chunk() {
+		let chunks =
+			chunks("#set text(lang: \"de\")\nHallo Welt.\n\n#text(lang: \"fr\")[Bonjour.]\n");
+		let languages = chunks
+			.iter()
+			.map(|chunk| chunk.language.as_deref())
+			.collect::<Vec<_>>();
+		assert_eq!(languages, [Some("de"), Some("fr"), Some("de")]);
+		assert_eq!(checked(&chunks[0]).trim(), "Hallo Welt.");
+	}
+}
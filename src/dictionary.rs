@@ -0,0 +1,46 @@
+use languagetool_rust::check::Match;
+use std::{collections::HashSet, error::Error, fs, path::PathBuf};
+
+/// Accepted words, one word per line
+pub struct Dictionary {
+	words: HashSet<String>,
+}
+
+impl Dictionary {
+	pub fn load(paths: &[PathBuf]) -> Result<Self, Box<dyn Error>> {
+		let mut words = HashSet::new();
+		for path in paths {
+			let text = fs::read_to_string(path)
+				.map_err(|err| format!("Failed to read dictionary {}: {}", path.display(), err))?;
+			words.extend(
+				text.lines()
+					.map(str::trim)
+					.filter(|word| !word.is_empty())
+					.map(String::from),
+			);
+		}
+		Ok(Self { words })
+	}
+
+	/// Remove spelling matches for accepted words
+	pub fn filter(&self, matches: &mut Vec<Match>) {
+		if self.words.is_empty() {
+			return;
+		}
+		matches.retain(|info| !(is_spelling(info) && self.words.contains(&flagged(info))));
+	}
+}
+
+fn is_spelling(info: &Match) -> bool {
+	let id = &info.rule.id;
+	id.starts_with("MORFOLOGIK_") || id.starts_with("HUNSPELL_")
+}
+
+fn flagged(info: &Match) -> String {
+	info.context
+		.text
+		.chars()
+		.skip(info.context.offset)
+		.take(info.context.length)
+		.collect()
+}
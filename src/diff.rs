@@ -0,0 +1,141 @@
+use languagetool_rust::check::Match;
+use std::{
+	collections::HashMap,
+	error::Error,
+	fs,
+	ops::RangeInclusive,
+	path::{Path, PathBuf},
+	process::Command,
+};
+
+use crate::{convert::Chunk, output::Lines};
+
+/// Changed lines per file from a unified diff
+pub struct Changes {
+	files: HashMap<PathBuf, Vec<RangeInclusive<usize>>>,
+}
+
+impl Changes {
+	/// Lines changed in the working tree relative to `rev`, the repository is found from `path`
+	pub fn git(rev: &str, path: &Path) -> Result<Self, Box<dyn Error>> {
+		let dir = if path.is_dir() {
+			path
+		} else {
+			path.parent().unwrap_or(Path::new("."))
+		};
+		let dir = if dir.as_os_str().is_empty() {
+			Path::new(".")
+		} else {
+			dir
+		};
+		let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+		let diff = git(
+			dir,
+			&["diff", "-U0", "--no-color", "--no-ext-diff", rev, "--"],
+		)?;
+		Ok(Self::parse(&diff, Path::new(root.trim())))
+	}
+
+	/// Lines changed in a diff file, paths are relative to the current folder
+	pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+		let diff = fs::read_to_string(path)
+			.map_err(|err| format!("Failed to read diff {}: {}", path.display(), err))?;
+		Ok(Self::parse(&diff, Path::new(".")))
+	}
+
+	fn parse(diff: &str, base: &Path) -> Self {
+		let mut files: HashMap<PathBuf, Vec<_>> = HashMap::new();
+		let mut current = None;
+		for line in diff.lines() {
+			if let Some(name) = line.strip_prefix("+++ ") {
+				let name = name.split('\t').next().unwrap_or(name);
+				current = match name {
+					"/dev/null" => None,
+					name => {
+						let name = name.strip_prefix("b/").unwrap_or(name);
+						let path = base.join(name);
+						Some(fs::canonicalize(&path).unwrap_or(path))
+					},
+				};
+			} else if let (Some(file), Some(hunk)) = (&current, line.strip_prefix("@@ ")) {
+				if let Some(lines) = hunk_lines(hunk) {
+					files.entry(file.clone()).or_default().push(lines);
+				}
+			}
+		}
+		Self { files }
+	}
+
+	/// Remove matches outside of the changed lines
+	pub fn filter(&self, file: &Path, lines: &Lines, chunk: &Chunk, matches: &mut Vec<Match>) {
+		let file = fs::canonicalize(file).unwrap_or(file.to_owned());
+		let changed = match self.files.get(&file) {
+			Some(changed) => changed,
+			None => {
+				matches.clear();
+				return;
+			},
+		};
+		matches.retain(|info| {
+			let (start, end) = lines.locate(chunk, info);
+			changed
+				.iter()
+				.any(|range| *range.start() <= end.line && start.line <= *range.end())
+		});
+	}
+}
+
+/// Lines of the new file in a hunk header `-a,b +c,d @@`, removed lines mark the lines around them
+fn hunk_lines(hunk: &str) -> Option<RangeInclusive<usize>> {
+	let new = hunk.split(' ').find_map(|part| part.strip_prefix('+'))?;
+	let (start, count) = match new.split_once(',') {
+		Some((start, count)) => (start.parse::<usize>().ok()?, count.parse::<usize>().ok()?),
+		None => (new.parse::<usize>().ok()?, 1),
+	};
+	if count == 0 {
+		return Some(start.max(1)..=start + 1);
+	}
+	Some(start..=start + count - 1)
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, Box<dyn Error>> {
+	let output = Command::new("git")
+		.arg("-C")
+		.arg(dir)
+		.args(args)
+		.output()
+		.map_err(|err| format!("Failed to run git: {}", err))?;
+	if !output.status.success() {
+		return Err(format!(
+			"git {} failed: {}",
+			args.join(" "),
+			String::from_utf8_lossy(&output.stderr).trim()
+		)
+		.into());
+	}
+	Ok(String::from_utf8(output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn hunks() {
+		assert_eq!(hunk_lines("-1,2 +1,3 @@"), Some(1..=3));
+		assert_eq!(hunk_lines("-5 +7 @@ fn main() {"), Some(7..=7));
+		assert_eq!(hunk_lines("-3,4 +10,1 @@"), Some(10..=10));
+	}
+
+	#[test]
+	fn removed_lines() {
+		assert_eq!(hunk_lines("-4,2 +3,0 @@"), Some(3..=4));
+		assert_eq!(hunk_lines("-1,2 +0,0 @@"), Some(1..=1));
+	}
+
+	#[test]
+	fn invalid() {
+		assert_eq!(hunk_lines("-1,2 @@"), None);
+		assert_eq!(hunk_lines("-1 +x,2 @@"), None);
+	}
+}
@@ -0,0 +1,89 @@
+use languagetool_rust::check::CheckRequest;
+use std::error::Error;
+
+use crate::{client::Client, dictionary::Dictionary, rules::Rules, Args};
+
+/// Check the connection to the server and the settings, every problem is reported
+pub async fn doctor(args: Args) -> Result<(), Box<dyn Error>> {
+	let client = Client::new(&args);
+	let server = if args.port.is_empty() {
+		args.host.clone()
+	} else {
+		format!("{}:{}", args.host, args.port)
+	};
+	let mut problems = 0;
+	let mut report = |result: Result<String, String>| match result {
+		Ok(message) => println!("ok    {}", message),
+		Err(message) => {
+			println!("error {}", message);
+			problems += 1;
+		},
+	};
+
+	match client.languages().await {
+		Ok(languages) => {
+			report(Ok(format!(
+				"Server {} reachable, {} languages",
+				server,
+				languages.len()
+			)));
+
+			let request = CheckRequest::default()
+				.with_language(String::from("en-US"))
+				.with_text(String::from("a"));
+			report(client.check(request).await.map(|response| {
+				format!("{} {}", response.software.name, response.software.version)
+			}));
+
+			match args.language.as_deref() {
+				None | Some("auto") => report(Ok(String::from(
+					"Language is auto-detected, set `--language` for more checks",
+				))),
+				Some(language) => {
+					let supported = languages.iter().any(|supported| {
+						supported.code.eq_ignore_ascii_case(language)
+							|| supported.long_code.eq_ignore_ascii_case(language)
+					});
+					if supported {
+						report(Ok(format!("Language {} supported", language)));
+					} else {
+						let codes = languages
+							.iter()
+							.map(|supported| supported.long_code.as_str())
+							.collect::<Vec<_>>();
+						report(Err(format!(
+							"Language {} not supported, supported are {}",
+							language,
+							codes.join(", ")
+						)));
+					}
+				},
+			}
+		},
+		Err(err) => report(Err(format!("Server {} unreachable: {}", server, err))),
+	}
+
+	if let Some(path) = &args.rules {
+		report(match Rules::load(path) {
+			Ok(rules) => Ok(format!(
+				"Rules file {} with {} functions",
+				path,
+				rules.functions.len()
+			)),
+			Err(err) => Err(format!("Invalid rules file {}: {}", path, err)),
+		});
+	}
+
+	if !args.dictionaries.is_empty() {
+		report(
+			Dictionary::load(&args.dictionaries)
+				.map(|_| format!("{} dictionaries loaded", args.dictionaries.len()))
+				.map_err(|err| err.to_string()),
+		);
+	}
+
+	if problems > 0 {
+		return Err(format!("{} problems found", problems).into());
+	}
+	Ok(())
+}
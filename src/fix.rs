@@ -0,0 +1,127 @@
+use std::{
+	error::Error,
+	fs,
+	io::{stdin, stdout, Write},
+	ops::Range,
+};
+
+use languagetool_rust::check::Match;
+
+use crate::{
+	cache::Cache,
+	client::Client,
+	convert::Chunk,
+	output::{self, Lines, Position},
+	Args,
+};
+
+struct Edit {
+	/// Byte range in the source
+	range: Range<usize>,
+	/// Start of `range`, for messages
+	position: Position,
+	replacement: String,
+}
+
+enum Choice {
+	Replace(usize),
+	Skip,
+	Quit,
+}
+
+/// Apply replacements to the source, asking for every match unless `--apply-first` is used
+pub async fn fix(args: Args) -> Result<(), Box<dyn Error>> {
+	let client = Client::new(&args);
+	let file = &args.path;
+	if !file.is_file() {
+		return Err(format!("{} is not a file", file.display()).into());
+	}
+	let mut text = fs::read_to_string(file)?;
+	let results = crate::check_text(&client, &args, &text, &mut Cache::new(&args)).await?;
+	let file_name = format!("{}", file.display());
+	let interactive = args.apply_first.is_empty();
+
+	let mut edits = Vec::new();
+	let lines = Lines::new(&text);
+	'chunks: for (matches, chunk) in &results {
+		for info in matches {
+			if info.replacements.is_empty() || !chunk.map.is_text(info.offset, info.length) {
+				continue;
+			}
+
+			let index = if interactive {
+				match prompt(&file_name, &lines, chunk, info)? {
+					Choice::Replace(index) => index,
+					Choice::Skip => continue,
+					Choice::Quit => break 'chunks,
+				}
+			} else if args.apply_first.contains(&info.rule.id) {
+				0
+			} else {
+				continue;
+			};
+
+			let range = chunk.map.range(info.offset, info.length, &text);
+			edits.push(Edit {
+				position: lines.position(range.start),
+				range,
+				replacement: info.replacements[index].value.clone(),
+			});
+		}
+	}
+
+	// footnotes are checked after their paragraph, so the chunks are not in source order
+	edits.sort_by_key(|edit| edit.range.start);
+	let mut applied = 0;
+	let mut limit = text.len();
+	for edit in edits.iter().rev() {
+		if edit.range.end > limit {
+			println!(
+				"Skipped fix at {}:{}:{}, it overlaps another fix",
+				file_name, edit.position.line, edit.position.column
+			);
+			continue;
+		}
+		text.replace_range(edit.range.clone(), &edit.replacement);
+		limit = edit.range.start;
+		applied += 1;
+	}
+	if applied > 0 {
+		fs::write(file, text)?;
+	}
+	println!("Applied {} fixes to {}", applied, file_name);
+	Ok(())
+}
+
+fn prompt(
+	file_name: &str,
+	lines: &Lines,
+	chunk: &Chunk,
+	info: &Match,
+) -> Result<Choice, Box<dyn Error>> {
+	output::print_pretty(file_name, lines, chunk, info);
+	for (index, replacement) in info.replacements.iter().enumerate() {
+		println!("{}: {}", index + 1, replacement.value);
+	}
+	loop {
+		print!(
+			"Apply replacement (1-{}), skip (s) or quit (q): ",
+			info.replacements.len()
+		);
+		stdout().flush()?;
+		let mut line = String::new();
+		if stdin().read_line(&mut line)? == 0 {
+			return Ok(Choice::Quit);
+		}
+		match line.trim() {
+			"" | "s" => return Ok(Choice::Skip),
+			"q" => return Ok(Choice::Quit),
+			value => match value.parse::<usize>() {
+				Ok(number) if (1..=info.replacements.len()).contains(&number) => {
+					return Ok(Choice::Replace(number - 1))
+				},
+				_ => continue,
+			},
+		}
+	}
+}
@@ -0,0 +1,211 @@
+use std::{collections::HashMap, time::Duration};
+use tokio::{sync::Mutex, time::sleep};
+
+use languagetool_rust::check::Match;
+use tower_lsp::{
+	jsonrpc::Result,
+	lsp_types::{
+		CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+		CodeActionProviderCapability, CodeActionResponse, Diagnostic, DiagnosticSeverity,
+		DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+		InitializeParams, InitializeResult, MessageType, NumberOrString, Range, ServerCapabilities,
+		ServerInfo, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url, WorkspaceEdit,
+	},
+	Client, LanguageServer, LspService, Server,
+};
+
+use crate::{
+	cache::Cache,
+	client,
+	convert::Chunk,
+	output::{Lines, Position},
+	severity::{self, Severity},
+	Args,
+};
+
+const SOURCE: &str = "typst-lt";
+
+pub async fn run(args: Args) {
+	let server = client::Client::new(&args);
+	let (service, socket) = LspService::new(|client| Backend {
+		client,
+		server,
+		args,
+		caches: Mutex::new(HashMap::new()),
+		generations: Mutex::new(HashMap::new()),
+	});
+	Server::new(tokio::io::stdin(), tokio::io::stdout(), socket)
+		.serve(service)
+		.await;
+}
+
+struct Backend {
+	client: Client,
+	server: client::Client,
+	args: Args,
+	caches: Mutex<HashMap<Url, Cache>>,
+	/// Counts the changes of open documents, to drop outdated checks
+	generations: Mutex<HashMap<Url, u64>>,
+}
+
+impl Backend {
+	/// Check the document after `--delay` without newer changes
+	async fn check(&self, uri: Url, text: String, version: Option<i32>) {
+		let generation = {
+			let mut generations = self.generations.lock().await;
+			let generation = generations.entry(uri.clone()).or_default();
+			*generation += 1;
+			*generation
+		};
+		sleep(Duration::from_secs_f64(self.args.delay)).await;
+		if !self.is_current(&uri, generation).await {
+			return;
+		}
+
+		// the cache is taken out, so other documents are checked meanwhile
+		let cache = self.caches.lock().await.remove(&uri);
+		let mut cache = cache.unwrap_or_else(|| Cache::new(&self.args));
+		let responses = crate::check_text(&self.server, &self.args, &text, &mut cache)
+			.await
+			.map_err(|err| err.to_string());
+		if self.generations.lock().await.contains_key(&uri) {
+			self.caches.lock().await.insert(uri.clone(), cache);
+		}
+		if !self.is_current(&uri, generation).await {
+			return;
+		}
+		match responses {
+			Ok(responses) => {
+				let diagnostics = diagnostics(&self.args, &text, &responses);
+				self.client
+					.publish_diagnostics(uri, diagnostics, version)
+					.await;
+			},
+			Err(err) => self.client.show_message(MessageType::ERROR, err).await,
+		}
+	}
+
+	/// No change arrived since `generation` and the document is still open
+	async fn is_current(&self, uri: &Url, generation: u64) -> bool {
+		self.generations.lock().await.get(uri) == Some(&generation)
+	}
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+	async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+		Ok(InitializeResult {
+			capabilities: ServerCapabilities {
+				text_document_sync: Some(TextDocumentSyncCapability::Kind(
+					TextDocumentSyncKind::FULL,
+				)),
+				code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+				..Default::default()
+			},
+			server_info: Some(ServerInfo {
+				name: String::from(SOURCE),
+				version: Some(String::from(env!("CARGO_PKG_VERSION"))),
+			}),
+		})
+	}
+
+	async fn shutdown(&self) -> Result<()> {
+		Ok(())
+	}
+
+	async fn did_open(&self, params: DidOpenTextDocumentParams) {
+		let document = params.text_document;
+		self.check(document.uri, document.text, Some(document.version))
+			.await;
+	}
+
+	async fn did_change(&self, mut params: DidChangeTextDocumentParams) {
+		// full sync, the last change contains the whole document
+		if let Some(change) = params.content_changes.pop() {
+			let document = params.text_document;
+			self.check(document.uri, change.text, Some(document.version))
+				.await;
+		}
+	}
+
+	async fn did_close(&self, params: DidCloseTextDocumentParams) {
+		self.generations
+			.lock()
+			.await
+			.remove(&params.text_document.uri);
+		self.caches.lock().await.remove(&params.text_document.uri);
+		self.client
+			.publish_diagnostics(params.text_document.uri, Vec::new(), None)
+			.await;
+	}
+
+	async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+		let mut actions = Vec::new();
+		for diagnostic in params.context.diagnostics {
+			if diagnostic.source.as_deref() != Some(SOURCE) {
+				continue;
+			}
+			let replacements = match &diagnostic.data {
+				Some(data) => {
+					serde_json::from_value::<Vec<String>>(data.clone()).unwrap_or_default()
+				},
+				None => continue,
+			};
+			for replacement in replacements {
+				let edit = TextEdit::new(diagnostic.range, replacement.clone());
+				let changes = HashMap::from([(params.text_document.uri.clone(), vec![edit])]);
+				actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+					title: format!("Replace with \"{}\"", replacement),
+					kind: Some(CodeActionKind::QUICKFIX),
+					diagnostics: Some(vec![diagnostic.clone()]),
+					edit: Some(WorkspaceEdit {
+						changes: Some(changes),
+						..Default::default()
+					}),
+					..Default::default()
+				}));
+			}
+		}
+		Ok(Some(actions))
+	}
+}
+
+fn diagnostics(args: &Args, text: &str, results: &[(Vec<Match>, Chunk)]) -> Vec<Diagnostic> {
+	let lines = Lines::new(text);
+	let mut diagnostics = Vec::new();
+	for (matches, chunk) in results {
+		for info in matches {
+			let (start, end) = lines.locate(chunk, info);
+			// replacing markup would break the document
+			let replacements = if chunk.map.is_text(info.offset, info.length) {
+				info.replacements
+					.iter()
+					.map(|replacement| replacement.value.as_str())
+					.collect::<Vec<_>>()
+			} else {
+				Vec::new()
+			};
+			diagnostics.push(Diagnostic {
+				range: Range::new(lsp_position(&start), lsp_position(&end)),
+				severity: Some(match severity::severity(args, info) {
+					Severity::Info => DiagnosticSeverity::INFORMATION,
+					Severity::Warning => DiagnosticSeverity::WARNING,
+					Severity::Error => DiagnosticSeverity::ERROR,
+				}),
+				code: Some(NumberOrString::String(info.rule.id.clone())),
+				source: Some(String::from(SOURCE)),
+				message: info.message.clone(),
+				data: Some(serde_json::json!(replacements)),
+				..Default::default()
+			});
+		}
+	}
+	diagnostics
+}
+
+fn lsp_position(position: &Position) -> tower_lsp::lsp_types::Position {
+	tower_lsp::lsp_types::Position::new(
+		(position.line - 1) as u32,
+		(position.utf16_column - 1) as u32,
+	)
+}
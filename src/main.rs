@@ -1,20 +1,45 @@
+mod baseline;
+mod cache;
+mod client;
+mod config;
 mod convert;
+mod dictionary;
+mod diff;
+mod doctor;
+mod fix;
+mod lsp;
 mod output;
+mod project;
+mod request;
 mod rules;
+mod server;
+mod severity;
+mod source_map;
+mod suppression;
 
-use clap::{Parser, ValueEnum};
-use languagetool_rust::{
-	check::{CheckRequest, Data},
-	server::ServerClient,
-};
+use baseline::Baseline;
+use cache::Cache;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
+use client::Client;
+use config::Config;
+use convert::Chunk;
+use dictionary::Dictionary;
+use diff::Changes;
+use futures::{stream, StreamExt};
+use languagetool_rust::check::Match;
 use notify::RecursiveMode;
 use notify_debouncer_mini::new_debouncer;
-use output::Position;
+use output::{Lines, Sarif};
+use request::Batch;
 use rules::Rules;
+use server::Server;
+use severity::{FailOn, Severity, Summary};
 use std::{
+	collections::{HashMap, HashSet},
 	error::Error,
 	fs,
 	path::{Path, PathBuf},
+	process::ExitCode,
 	time::Duration,
 };
 
@@ -22,13 +47,28 @@ use std::{
 enum Task {
 	Check,
 	Watch,
+	Lsp,
+	Fix,
+	CacheClear,
+	Server,
+	Doctor,
+	RulesDump,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+	Pretty,
+	Plain,
+	Json,
+	Sarif,
 }
 
 #[derive(Parser, Debug)]
 struct Args {
 	task: Task,
 
-	/// File to check, may be a folder with `watch`
+	/// File or folder to check, included and imported files are checked as well. Ignored with `lsp`
+	#[clap(default_value = ".")]
 	path: PathBuf,
 
 	/// Document Language. Defaults to auto-detect, but explicit codes ("de-DE", "en-US", ...) enable more checks
@@ -39,10 +79,14 @@ struct Args {
 	#[clap(short, long, default_value_t = 0.1)]
 	delay: f64,
 
-	/// Print results without annotations for easy regex evaluation
+	/// Print results without annotations for easy regex evaluation, same as `--output-format=plain`
 	#[clap(short, long, default_value_t = false)]
 	plain: bool,
 
+	/// Output format, `json` prints one object per match, `sarif` one report for all files
+	#[clap(short, long, value_enum, default_value_t = OutputFormat::Pretty)]
+	output_format: OutputFormat,
+
 	/// Server Address
 	#[clap(short = 'H', long, default_value = "http://127.0.0.1")]
 	host: String,
@@ -51,49 +95,278 @@ struct Args {
 	#[clap(short = 'P', long, default_value = "8081")]
 	port: String,
 
-	/// Split long documents into smaller chunks
+	/// Maximal length of the paragraphs sent in one request
 	#[clap(long, default_value_t = 10_000)]
 	max_request_length: usize,
 
+	/// Maximal number of requests sent at the same time
+	#[clap(long, default_value_t = 4)]
+	concurrency: usize,
+
 	/// Overwrite `host`, `port` and `max-request-length` to the official API at `https://api.languagetoolplus.com`
 	#[clap(long, default_value_t = false)]
 	use_official_api: bool,
 
+	/// Start a LanguageTool server on a free port for this run
+	#[clap(long, default_value_t = false)]
+	spawn_server: bool,
+
+	/// Path to `languagetool-server.jar` or the LanguageTool folder, used by `server` and `--spawn-server`
+	#[clap(long, env = "LANGUAGETOOL_JAR", default_value = None)]
+	server_jar: Option<PathBuf>,
+
+	/// Username for premium access to the official API
+	#[clap(long, env = "LANGUAGETOOL_USERNAME")]
+	username: Option<String>,
+
+	/// API key for premium access to the official API
+	#[clap(long, env = "LANGUAGETOOL_API_KEY")]
+	api_key: Option<String>,
+
+	/// Maximal number of requests per minute. Defaults to 20 for the official API without premium access
+	#[clap(long, default_value = None)]
+	requests_per_minute: Option<usize>,
+
+	/// Maximal number of characters per minute. Defaults to 75000 for the official API without premium access
+	#[clap(long, default_value = None)]
+	chars_per_minute: Option<usize>,
+
+	/// Retries with increasing delay when the server is unreachable, overloaded or reports an error
+	#[clap(long, default_value_t = 3)]
+	retries: u32,
+
 	/// Path to rules file
 	#[clap(short, long, default_value = None)]
 	rules: Option<String>,
+
+	/// Do not use the built-in rules for the functions of the standard library
+	#[clap(long, default_value_t = false)]
+	no_default_rules: bool,
+
+	/// Files with accepted words, one word per line
+	#[clap(long = "dictionary")]
+	dictionaries: Vec<PathBuf>,
+
+	/// With `fix`, apply the first replacement for these comma separated rule ids without asking
+	#[clap(long, value_delimiter = ',')]
+	apply_first: Vec<String>,
+
+	/// Comma separated LanguageTool rule ids to enable
+	#[clap(long, value_delimiter = ',')]
+	enabled_rules: Vec<String>,
+
+	/// Comma separated LanguageTool rule ids to disable
+	#[clap(long, value_delimiter = ',')]
+	disabled_rules: Vec<String>,
+
+	/// Comma separated LanguageTool category ids to enable
+	#[clap(long, value_delimiter = ',')]
+	enabled_categories: Vec<String>,
+
+	/// Comma separated LanguageTool category ids to disable
+	#[clap(long, value_delimiter = ',')]
+	disabled_categories: Vec<String>,
+
+	/// Only use the enabled rules and categories
+	#[clap(long, default_value_t = false)]
+	enabled_only: bool,
+
+	/// Comma separated `CATEGORY=SEVERITY` pairs, categories default to `warning`
+	#[clap(long = "severity", value_delimiter = ',', value_parser = severity::parse)]
+	severities: Vec<(String, Severity)>,
+
+	/// With `check`, exit with code 1 for matches with this severity or higher
+	#[clap(long, value_enum, default_value_t = FailOn::Warning)]
+	fail_on: FailOn,
+
+	/// With `check`, number of failing matches below `error` allowed before exiting with code 1
+	#[clap(long, default_value_t = 0)]
+	max_warnings: usize,
+
+	/// With `check`, only report matches not found in this file
+	#[clap(long, default_value = None)]
+	baseline: Option<PathBuf>,
+
+	/// With `check`, write all matches to the `--baseline` file
+	#[clap(long, default_value_t = false)]
+	write_baseline: bool,
+
+	/// With `check`, only report matches in lines changed relative to this git revision
+	#[clap(long, default_value = None)]
+	diff: Option<String>,
+
+	/// With `check`, only report matches in lines changed by this unified diff
+	#[clap(long, default_value = None)]
+	diff_file: Option<PathBuf>,
+
+	/// Do not store results on disk
+	#[clap(long, default_value_t = false)]
+	no_cache: bool,
+
+	/// Folder for stored results, defaults to `typst-lt` in the cache folder of the user
+	#[clap(long, default_value = None)]
+	cache_dir: Option<PathBuf>,
+
+	/// Maximal size of stored results in MB
+	#[clap(long, default_value_t = 100)]
+	cache_size: u64,
+
+	/// Path to config file. Defaults to the first `typst-lt.toml` found in the folder of `path` or its parents
+	#[clap(short, long, default_value = None)]
+	config: Option<PathBuf>,
+
+	/// Function rules from the config file, overwritten by the rules file
+	#[clap(skip)]
+	functions: HashMap<String, rules::Function>,
+
+	/// Checked parts of code from the config file, overwritten by the rules file
+	#[clap(skip)]
+	code: Option<rules::Code>,
+
+	/// Checked text for equations from the config file, overwritten by the rules file
+	#[clap(skip)]
+	math: Option<rules::Math>,
 }
 
+/// Exit code when checking failed, matches exit with code 1
+const ERROR_CODE: u8 = 2;
+
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-	let mut args = Args::parse();
+async fn main() -> ExitCode {
+	match run().await {
+		Ok(code) => code,
+		Err(err) => {
+			eprintln!("Error: {}", err);
+			ExitCode::from(ERROR_CODE)
+		},
+	}
+}
+
+async fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
+	let matches = Args::command().get_matches();
+	let mut args = Args::from_arg_matches(&matches)?;
+
+	let config = match &args.config {
+		Some(path) => Some(path.clone()),
+		None => Config::find(&args.path),
+	};
+	if let Some(path) = config {
+		Config::load(&path)?.apply(&mut args, &matches);
+	}
 
 	if args.use_official_api {
 		args.host = String::from("https://api.languagetoolplus.com");
 		args.port = String::new();
 		args.max_request_length = 1_000;
 	}
+	if args.plain {
+		args.output_format = OutputFormat::Plain;
+	}
+
+	let _server = match args.task {
+		Task::Check | Task::Watch | Task::Lsp | Task::Fix | Task::Doctor if args.spawn_server => {
+			if args.use_official_api {
+				return Err("`--spawn-server` can not be used with `--use-official-api`".into());
+			}
+			Some(Server::spawn(&mut args, server::free_port()?).await?)
+		},
+		_ => None,
+	};
 
 	match args.task {
-		Task::Check => check(args).await?,
+		Task::Check => return check(args).await,
 		Task::Watch => watch(args).await?,
+		Task::Lsp => lsp::run(args).await,
+		Task::Fix => fix::fix(args).await?,
+		Task::CacheClear => cache::clear(&args)?,
+		Task::Server => server::run(args).await?,
+		Task::Doctor => doctor::doctor(args).await?,
+		Task::RulesDump => rules::dump(&args)?,
 	}
-	Ok(())
+	Ok(ExitCode::SUCCESS)
 }
 
-async fn check(args: Args) -> Result<(), Box<dyn std::error::Error>> {
-	let client = ServerClient::new(&args.host, &args.port);
-	handle_file(&client, &args, &args.path).await?;
-	Ok(())
+async fn check(args: Args) -> Result<ExitCode, Box<dyn std::error::Error>> {
+	let client = Client::new(&args);
+	let (root, mut pending) = if args.path.is_dir() {
+		(args.path.clone(), project::files(&args.path)?)
+	} else {
+		let root = args.path.parent().unwrap_or(Path::new("."));
+		(root.to_path_buf(), vec![args.path.clone()])
+	};
+	pending.reverse();
+	let baseline = match &args.baseline {
+		Some(path) if !args.write_baseline => Some(Baseline::load(path, &root)?),
+		None if args.write_baseline => {
+			return Err("`--write-baseline` needs `--baseline=<file>`".into())
+		},
+		_ => None,
+	};
+	let changes = match (&args.diff, &args.diff_file) {
+		(Some(rev), _) => Some(Changes::git(rev, &args.path)?),
+		(None, Some(path)) => Some(Changes::load(path)?),
+		(None, None) => None,
+	};
+	let mut filters = Filters { baseline, changes };
+	let mut written = Baseline::new(&root);
+	let mut checked = HashSet::new();
+	let mut sarif = Sarif::new();
+	let mut cache = Cache::new(&args);
+	let mut summary = Summary::default();
+
+	if args.output_format == OutputFormat::Plain {
+		println!("START");
+	}
+	while let Some(file) = pending.pop() {
+		if !checked.insert(fs::canonicalize(&file)?) {
+			continue;
+		}
+		let text = fs::read_to_string(&file)?;
+		let results = handle_file(
+			&client,
+			&args,
+			&file,
+			&text,
+			&mut sarif,
+			&mut cache,
+			&mut filters,
+		)
+		.await?;
+		for (matches, chunk) in &results {
+			summary.add(&args, matches);
+			written.add(&file, chunk, matches);
+		}
+
+		let root_node = typst_syntax::parse(&text);
+		let mut dependencies = project::dependencies(&root_node, &file, &root);
+		dependencies.reverse();
+		pending.extend(dependencies);
+	}
+	if args.output_format == OutputFormat::Plain {
+		println!("END");
+	}
+	if args.output_format == OutputFormat::Sarif {
+		sarif.print();
+	}
+	if let Some(path) = args.baseline.as_ref().filter(|_| args.write_baseline) {
+		written.write(path)?;
+		return Ok(ExitCode::SUCCESS);
+	}
+	if summary.failed(&args) {
+		summary.print();
+		return Ok(ExitCode::FAILURE);
+	}
+	Ok(ExitCode::SUCCESS)
 }
 
 async fn watch(args: Args) -> Result<(), Box<dyn std::error::Error>> {
 	let (tx, rx) = std::sync::mpsc::channel();
-	let client = ServerClient::new(&args.host, &args.port);
+	let client = Client::new(&args);
 	let mut watcher = new_debouncer(Duration::from_secs_f64(args.delay), None, tx)?;
 	watcher
 		.watcher()
 		.watch(&args.path, RecursiveMode::Recursive)?;
+	let mut caches = HashMap::<PathBuf, Cache>::new();
 
 	for events in rx {
 		for event in events.unwrap() {
@@ -101,50 +374,152 @@ async fn watch(args: Args) -> Result<(), Box<dyn std::error::Error>> {
 				Some(ext) if ext == "typ" => {},
 				_ => continue,
 			}
-			handle_file(&client, &args, &event.path)
+			let mut sarif = Sarif::new();
+			if args.output_format == OutputFormat::Plain {
+				println!("START");
+			}
+			let cache = caches
+				.entry(event.path.clone())
+				.or_insert_with(|| Cache::new(&args));
+			match fs::read_to_string(&event.path) {
+				Ok(text) => handle_file(
+					&client,
+					&args,
+					&event.path,
+					&text,
+					&mut sarif,
+					cache,
+					&mut Filters::default(),
+				)
 				.await
-				.unwrap_or_else(|err| println!("{}", err));
+				.map(|_| ()),
+				Err(err) => Err(err.into()),
+			}
+			.unwrap_or_else(|err| println!("{}", err));
+			if args.output_format == OutputFormat::Plain {
+				println!("END");
+			}
+			if args.output_format == OutputFormat::Sarif {
+				sarif.print();
+			}
 		}
 	}
 
 	Ok(())
 }
 
+/// Limit the reported matches of `check`
+#[derive(Default)]
+struct Filters {
+	baseline: Option<Baseline>,
+	changes: Option<Changes>,
+}
+
 async fn handle_file(
-	client: &ServerClient,
+	client: &Client,
 	args: &Args,
 	file: &Path,
-) -> Result<(), Box<dyn Error>> {
-	let text = fs::read_to_string(file)?;
-	let rules = match &args.rules {
-		None => Rules::new(),
-		Some(path) => Rules::load(path)?,
-	};
+	text: &str,
+	sarif: &mut Sarif,
+	cache: &mut Cache,
+	filters: &mut Filters,
+) -> Result<Vec<(Vec<Match>, Chunk)>, Box<dyn Error>> {
+	let mut results = check_text(client, args, text, cache).await?;
+
+	let lines = Lines::new(text);
+	for (matches, chunk) in &mut results {
+		if let Some(changes) = &filters.changes {
+			changes.filter(file, &lines, chunk, matches);
+		}
+		if let Some(baseline) = &mut filters.baseline {
+			baseline.filter(file, chunk, matches);
+		}
+	}
+	for (matches, chunk) in &results {
+		match args.output_format {
+			OutputFormat::Pretty => output::output_pretty(file, &lines, matches, chunk),
+			OutputFormat::Plain => output::output_plain(args, file, &lines, matches, chunk),
+			OutputFormat::Json => output::output_json(args, file, &lines, matches, chunk),
+			OutputFormat::Sarif => sarif.add(args, file, &lines, matches, chunk),
+		}
+	}
+	Ok(results)
+}
+
+async fn check_text(
+	client: &Client,
+	args: &Args,
+	text: &str,
+	cache: &mut Cache,
+) -> Result<Vec<(Vec<Match>, Chunk)>, Box<dyn Error>> {
+	let rules = Rules::from_args(args)?;
 
-	let root = typst_syntax::parse(&text);
-	let data = convert::convert(&root, &rules, args.max_request_length);
+	let dictionary = Dictionary::load(&args.dictionaries)?;
 
-	if args.plain {
-		println!("START");
+	let root = typst_syntax::parse(text);
+	let (paragraphs, suppressions) = convert::convert(&root, &rules);
+
+	cache.prepare(client, args).await?;
+	let languages = paragraphs
+		.iter()
+		.map(|paragraph| language(&paragraph.language, &args.language))
+		.collect::<Vec<_>>();
+	let keys = paragraphs
+		.iter()
+		.zip(&languages)
+		.map(|(paragraph, language)| cache.key(language, &paragraph.annotations))
+		.collect::<Vec<_>>();
+	let mut results = keys.iter().map(|key| cache.get(key)).collect::<Vec<_>>();
+
+	let mut batches = Vec::<Batch>::new();
+	for (index, paragraph) in paragraphs.iter().enumerate() {
+		if results[index].is_some() {
+			continue;
+		}
+		let fits = batches.last().is_some_and(|batch| {
+			batch.language == languages[index] && batch.length <= args.max_request_length
+		});
+		if !fits {
+			batches.push(Batch::new(languages[index].clone()));
+		}
+		batches.last_mut().unwrap().push(index, paragraph);
 	}
-	let mut position = Position::new(&text);
-	for items in data {
-		let req = CheckRequest::default()
-			.with_language(match &args.language {
-				Some(value) => value.clone(),
-				None => "auto".into(),
-			})
-			.with_data(Data::from_iter(items.0));
-
-		let response = &client.check(&req).await?;
-		if args.plain {
-			output::output_plain(file, &mut position, response, items.1);
-		} else {
-			output::output_pretty(file, &mut position, response, items.1);
+
+	let requests = batches
+		.iter()
+		.map(|batch| batch.request(args))
+		.collect::<Vec<_>>();
+	let responses = stream::iter(requests)
+		.map(|req| async move { client.check(req).await })
+		.buffered(args.concurrency.max(1))
+		.collect::<Vec<_>>()
+		.await;
+	for (batch, response) in batches.iter().zip(responses) {
+		for (index, matches) in batch.split(response?.matches) {
+			cache.insert(keys[index].clone(), matches.clone());
+			results[index] = Some(matches);
 		}
 	}
-	if args.plain {
-		println!("END");
+	cache.prune();
+
+	let mut checked = Vec::with_capacity(paragraphs.len());
+	for (paragraph, matches) in paragraphs.into_iter().zip(results) {
+		let mut matches = matches.unwrap_or_default();
+		dictionary.filter(&mut matches);
+		suppressions.filter(&mut matches, &paragraph, text);
+		checked.push((matches, paragraph));
+	}
+	Ok(checked)
+}
+
+/// Language set in the document, or the given language if it is a variant of it ("en-US" for "en")
+fn language(document: &Option<String>, default: &Option<String>) -> String {
+	match (document, default) {
+		(Some(lang), Some(default)) if default.starts_with(&format!("{}-", lang)) => {
+			default.clone()
+		},
+		(Some(lang), _) => lang.clone(),
+		(None, Some(default)) => default.clone(),
+		(None, None) => "auto".into(),
 	}
-	Ok(())
 }
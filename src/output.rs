@@ -1,66 +1,180 @@
-use std::{io::stdout, io::Write, path::Path, str::Chars};
+use std::{collections::HashMap, io::stdout, io::Write, path::Path};
 
 use annotate_snippets::{
 	display_list::{DisplayList, FormatOptions},
 	snippet::{Annotation, AnnotationType, Slice, Snippet, SourceAnnotation},
 };
-use languagetool_rust::{check::Match, CheckResponse};
+use languagetool_rust::check::Match;
+use serde_json::{json, Value};
 
-pub fn output_plain(file: &Path, start: &mut Position, response: &CheckResponse, total: usize) {
-	let mut last = 0;
+use crate::{convert::Chunk, severity, Args};
+
+pub fn output_plain(args: &Args, file: &Path, lines: &Lines, matches: &[Match], chunk: &Chunk) {
 	let mut out = stdout().lock();
-	for info in &response.matches {
-		start.advance(info.offset - last);
-		let mut end = start.clone();
-		end.advance(info.length);
+	for info in matches {
+		let (start, end) = lines.locate(chunk, info);
 		writeln!(
 			out,
-			"{} {}:{}-{}:{} info {}",
+			"{} {}:{}-{}:{} {} {}",
 			file.display(),
 			start.line,
 			start.column,
 			end.line,
 			end.column,
+			severity::severity(args, info).name(),
 			info.message,
 		)
 		.unwrap();
-		last = info.offset;
 	}
-	start.advance(total - last);
+}
+
+pub fn output_json(args: &Args, file: &Path, lines: &Lines, matches: &[Match], chunk: &Chunk) {
+	let mut out = stdout().lock();
+	let file_name = format!("{}", file.display());
+	for info in matches {
+		let (start, end) = lines.locate(chunk, info);
+		let replacements = info
+			.replacements
+			.iter()
+			.map(|replacement| &replacement.value)
+			.collect::<Vec<_>>();
+		let value = json!({
+			"file": file_name,
+			"start": { "line": start.line, "column": start.column, "offset": start.offset },
+			"end": { "line": end.line, "column": end.column, "offset": end.offset },
+			"rule": info.rule.id,
+			"category": info.rule.category.id,
+			"severity": severity::severity(args, info).name(),
+			"message": info.message,
+			"replacements": replacements,
+		});
+		writeln!(out, "{}", value).unwrap();
+	}
+}
+
+/// Collects matches of all checked files into one SARIF 2.1.0 report
+pub struct Sarif {
+	rules: Vec<Value>,
+	rule_indices: HashMap<String, usize>,
+	results: Vec<Value>,
+}
+
+impl Sarif {
+	pub fn new() -> Self {
+		Self {
+			rules: Vec::new(),
+			rule_indices: HashMap::new(),
+			results: Vec::new(),
+		}
+	}
+
+	pub fn add(
+		&mut self,
+		args: &Args,
+		file: &Path,
+		lines: &Lines,
+		matches: &[Match],
+		chunk: &Chunk,
+	) {
+		let uri = file.to_string_lossy().replace('\\', "/");
+		for info in matches {
+			let (start, end) = lines.locate(chunk, info);
+			let rule_index = self.rule_index(info);
+			self.results.push(json!({
+				"ruleId": info.rule.id,
+				"ruleIndex": rule_index,
+				"level": severity::severity(args, info).sarif_level(),
+				"message": { "text": info.message },
+				"locations": [{
+					"physicalLocation": {
+						"artifactLocation": { "uri": uri },
+						"region": {
+							"startLine": start.line,
+							"startColumn": start.utf16_column,
+							"endLine": end.line,
+							"endColumn": end.utf16_column,
+							"byteOffset": start.offset,
+							"byteLength": end.offset - start.offset,
+						},
+					},
+				}],
+			}));
+		}
+	}
+
+	fn rule_index(&mut self, info: &Match) -> usize {
+		if let Some(&index) = self.rule_indices.get(&info.rule.id) {
+			return index;
+		}
+		let mut rule = json!({
+			"id": info.rule.id,
+			"shortDescription": { "text": info.rule.description },
+			"properties": {
+				"category": info.rule.category.name,
+				"tags": [info.rule.category.id],
+			},
+		});
+		if let Some(url) = info.rule.urls.iter().flatten().next() {
+			rule["helpUri"] = json!(url.value);
+		}
+		let index = self.rules.len();
+		self.rules.push(rule);
+		self.rule_indices.insert(info.rule.id.clone(), index);
+		index
+	}
+
+	pub fn print(self) {
+		let report = json!({
+			"$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+			"version": "2.1.0",
+			"runs": [{
+				"tool": {
+					"driver": {
+						"name": "typst-lt",
+						"version": env!("CARGO_PKG_VERSION"),
+						"rules": self.rules,
+					},
+				},
+				"results": self.results,
+			}],
+		});
+		println!("{:#}", report);
+	}
 }
 
 const PRETTY_RANGE: usize = 20;
 
-pub fn output_pretty(file: &Path, start: &mut Position, response: &CheckResponse, total: usize) {
-	let mut last = 0;
+pub fn output_pretty(file: &Path, lines: &Lines, matches: &[Match], chunk: &Chunk) {
 	let file_name = format!("{}", file.display());
-	for info in &response.matches {
-		if info.offset > PRETTY_RANGE {
-			start.advance(info.offset - PRETTY_RANGE - last);
-			last = info.offset - PRETTY_RANGE;
-		}
-		print_pretty(&file_name, start, info);
+	for info in matches {
+		print_pretty(&file_name, lines, chunk, info);
 	}
-	start.advance(total - last);
 }
 
-fn print_pretty(file_name: &str, start: &Position, info: &Match) {
-	let start_buffer = info.offset.min(PRETTY_RANGE);
+pub fn print_pretty(file_name: &str, lines: &Lines, chunk: &Chunk, info: &Match) {
+	let range = chunk.map.range(info.offset, info.length, lines.text);
+	let context_start = lines.text[..range.start]
+		.char_indices()
+		.rev()
+		.take(PRETTY_RANGE)
+		.last()
+		.map_or(range.start, |(index, _)| index);
+	let start_buffer = lines.text[context_start..range.start].chars().count();
+	let length = lines.text[range].chars().count();
 
-	let context = start
-		.clone()
-		.content
-		.take(start_buffer + info.length + PRETTY_RANGE)
+	let context = lines.text[context_start..]
+		.chars()
+		.take(start_buffer + length + PRETTY_RANGE)
 		.collect::<String>();
 
 	let mut annotations = Vec::new();
 	annotations.push(SourceAnnotation {
 		label: &info.message,
 		annotation_type: AnnotationType::Info,
-		range: (start_buffer, start_buffer + info.length),
+		range: (start_buffer, start_buffer + length),
 	});
 	for replacement in &info.replacements {
-		let pos = start_buffer + info.length + 2;
+		let pos = start_buffer + length + 2;
 		annotations.push(SourceAnnotation {
 			label: &replacement.value,
 			annotation_type: AnnotationType::Help,
@@ -87,7 +201,7 @@ fn print_pretty(file_name: &str, start: &Position, info: &Match) {
 		footer: Vec::new(),
 		slices: vec![Slice {
 			source: &context,
-			line_start: start.line,
+			line_start: lines.position(context_start).line,
 			origin: Some(file_name),
 			fold: true,
 			annotations,
@@ -101,33 +215,44 @@ fn print_pretty(file_name: &str, start: &Position, info: &Match) {
 	println!("{}", DisplayList::from(snippet));
 }
 
-#[derive(Clone)]
-pub struct Position<'a> {
-	line: usize,
-	column: usize,
-	content: Chars<'a>,
+/// Line and column of a byte offset, starting at 1
+#[derive(Clone, Copy)]
+pub struct Position {
+	pub line: usize,
+	pub column: usize,
+	pub utf16_column: usize,
+	/// Byte offset in the source
+	pub offset: usize,
 }
 
-impl<'a> Position<'a> {
-	pub fn new(content: &'a str) -> Self {
-		Self {
-			line: 1,
-			column: 1,
-			content: content.chars(),
-		}
+/// Start of every line for position lookup
+pub struct Lines<'a> {
+	pub text: &'a str,
+	starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+	pub fn new(text: &'a str) -> Self {
+		let starts = std::iter::once(0)
+			.chain(text.match_indices('\n').map(|(index, _)| index + 1))
+			.collect();
+		Self { text, starts }
 	}
 
-	fn advance(&mut self, amount: usize) {
-		for _ in 0..amount {
-			match self.content.next().unwrap() {
-				'\n' => {
-					self.line += 1;
-					self.column = 1;
-				},
-				_ => {
-					self.column += 1;
-				},
-			}
+	pub fn position(&self, offset: usize) -> Position {
+		let line = self.starts.partition_point(|&start| start <= offset) - 1;
+		let before = &self.text[self.starts[line]..offset];
+		Position {
+			line: line + 1,
+			column: before.chars().count() + 1,
+			utf16_column: before.encode_utf16().count() + 1,
+			offset,
 		}
 	}
+
+	/// Start and end of a match in the source
+	pub fn locate(&self, chunk: &Chunk, info: &Match) -> (Position, Position) {
+		let range = chunk.map.range(info.offset, info.length, self.text);
+		(self.position(range.start), self.position(range.end))
+	}
 }
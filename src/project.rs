@@ -0,0 +1,60 @@
+use std::{
+	fs, io,
+	path::{Path, PathBuf},
+};
+
+use typst_syntax::{SyntaxKind, SyntaxNode};
+
+/// All `.typ` files in `dir` and its subfolders, sorted by path.
+pub fn files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+	let mut result = Vec::new();
+	for entry in fs::read_dir(dir)? {
+		let path = entry?.path();
+		if path.is_dir() {
+			result.extend(files(&path)?);
+		} else if is_typst(&path) {
+			result.push(path);
+		}
+	}
+	result.sort();
+	Ok(result)
+}
+
+/// Files referenced by `#include` and `#import` in `file`.
+/// Paths starting with `/` are resolved relative to `root`, packages are ignored.
+pub fn dependencies(node: &SyntaxNode, file: &Path, root: &Path) -> Vec<PathBuf> {
+	let dir = file.parent().unwrap_or(Path::new("."));
+	let mut dependencies = Vec::new();
+	collect(node, dir, root, &mut dependencies);
+	dependencies
+}
+
+fn collect(node: &SyntaxNode, dir: &Path, root: &Path, dependencies: &mut Vec<PathBuf>) {
+	match node.kind() {
+		SyntaxKind::ModuleInclude | SyntaxKind::ModuleImport => {
+			let source = node
+				.children()
+				.find(|child| child.kind() == SyntaxKind::Str);
+			if let Some(source) = source {
+				let path = source.text().trim_matches('"');
+				let path = match path.strip_prefix('/') {
+					Some(path) => root.join(path),
+					None if path.starts_with('@') => return,
+					None => dir.join(path),
+				};
+				if is_typst(&path) && path.is_file() {
+					dependencies.push(path);
+				}
+			}
+		},
+		_ => {
+			for child in node.children() {
+				collect(child, dir, root, dependencies);
+			}
+		},
+	}
+}
+
+fn is_typst(path: &Path) -> bool {
+	matches!(path.extension(), Some(ext) if ext == "typ")
+}
@@ -0,0 +1,67 @@
+use languagetool_rust::check::{CheckRequest, Data, DataAnnotation, Match};
+
+use crate::{convert::Chunk, Args};
+
+/// Paragraphs with the same language sent together in one request
+pub struct Batch {
+	pub language: String,
+	pub annotations: Vec<DataAnnotation>,
+	pub length: usize,
+	/// Index and start in the batch of every paragraph
+	paragraphs: Vec<(usize, usize)>,
+}
+
+impl Batch {
+	pub fn new(language: String) -> Self {
+		Self {
+			language,
+			annotations: Vec::new(),
+			length: 0,
+			paragraphs: Vec::new(),
+		}
+	}
+
+	pub fn push(&mut self, index: usize, chunk: &Chunk) {
+		self.paragraphs.push((index, self.length));
+		self.annotations.extend(chunk.annotations.iter().cloned());
+		self.length += chunk.length;
+	}
+
+	pub fn request(&self, args: &Args) -> CheckRequest {
+		let mut request = CheckRequest::default()
+			.with_language(self.language.clone())
+			.with_data(Data::from_iter(self.annotations.iter().cloned()));
+		request.enabled_rules = non_empty(&args.enabled_rules);
+		request.disabled_rules = non_empty(&args.disabled_rules);
+		request.enabled_categories = non_empty(&args.enabled_categories);
+		request.disabled_categories = non_empty(&args.disabled_categories);
+		request.enabled_only = args.enabled_only;
+		request
+	}
+
+	/// Distribute the matches of the batch to the paragraphs, with offsets relative to the paragraph
+	pub fn split(&self, matches: Vec<Match>) -> Vec<(usize, Vec<Match>)> {
+		let mut result = self
+			.paragraphs
+			.iter()
+			.map(|&(index, _)| (index, Vec::new()))
+			.collect::<Vec<_>>();
+		for mut info in matches {
+			let paragraph = self
+				.paragraphs
+				.partition_point(|&(_, start)| start <= info.offset)
+				.saturating_sub(1);
+			info.offset -= self.paragraphs[paragraph].1;
+			result[paragraph].1.push(info);
+		}
+		result
+	}
+}
+
+fn non_empty(ids: &[String]) -> Option<Vec<String>> {
+	if ids.is_empty() {
+		None
+	} else {
+		Some(ids.to_vec())
+	}
+}
@@ -1,20 +1,124 @@
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, error::Error, fs::File, io::BufReader};
+use std::{collections::BTreeMap, error::Error, fs::File, io::BufReader};
+
+use crate::Args;
+
+/// Rules for the functions of the standard library
+const DEFAULT_RULES: &str = include_str!("default_rules.json");
 
 #[derive(Serialize, Deserialize)]
 pub struct Rules {
-	pub functions: HashMap<String, Function>,
+	pub functions: BTreeMap<String, Function>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub code: Option<Code>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub math: Option<Math>,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Checked parts of code outside of function rules
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Code {
+	/// Content blocks `[..]`
+	pub content: bool,
+	/// String literals in let bindings and code blocks
+	pub strings: bool,
+}
+
+impl Default for Code {
+	fn default() -> Self {
+		Self { content: true, strings: false }
+	}
+}
+
+/// Checked text for equations
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Math {
+	/// Checked instead of inline equations `$x$`
+	pub inline: String,
+	/// Checked instead of display equations `$ x $`
+	pub display: String,
+	/// Check string literals `"text"` in equations
+	pub strings: bool,
+}
+
+impl Default for Math {
+	fn default() -> Self {
+		Self {
+			inline: String::from("X"),
+			display: String::from("\n\n"),
+			strings: false,
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
 pub struct Function {
+	/// Checked before the call
+	#[serde(skip_serializing_if = "String::is_empty")]
 	pub before: String,
+	/// Checked after the call
+	#[serde(skip_serializing_if = "String::is_empty")]
 	pub after: String,
+	/// Ignore the call
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	pub skip: bool,
+	/// Check this instead of the call
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub replace_with: Option<String>,
+	/// Only check content arguments `[..]`
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	pub check_content_only: bool,
+	/// Check these named string or content arguments
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub check_args: Vec<String>,
+	/// Check the call as its own paragraph, `replace_with` is checked in the surrounding text
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	pub separate: bool,
+	/// Check content arguments as part of the surrounding text, not as separate paragraphs
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	pub inline: bool,
+}
+
+impl Function {
+	/// Only some arguments are checked
+	pub fn is_selective(&self) -> bool {
+		self.check_content_only || !self.check_args.is_empty()
+	}
 }
 
 impl Rules {
 	pub fn new() -> Self {
-		Self { functions: HashMap::new() }
+		Self {
+			functions: BTreeMap::new(),
+			code: None,
+			math: None,
+		}
+	}
+
+	pub fn builtin() -> Self {
+		serde_json::from_str(DEFAULT_RULES).expect("invalid built-in rules")
+	}
+
+	/// Built-in rules, overwritten by the config file and the rules file
+	pub fn from_args(args: &Args) -> Result<Self, Box<dyn Error>> {
+		let mut rules = if args.no_default_rules {
+			Self::new()
+		} else {
+			Self::builtin()
+		};
+		rules.functions.extend(args.functions.clone());
+		rules.code = args.code.or(rules.code);
+		rules.math = args.math.clone().or(rules.math);
+		if let Some(path) = &args.rules {
+			let file = Self::load(path)?;
+			rules.functions.extend(file.functions);
+			rules.code = file.code.or(rules.code);
+			rules.math = file.math.or(rules.math);
+		}
+		Ok(rules)
 	}
 
 	pub fn load(path: &String) -> Result<Self, Box<dyn Error>> {
@@ -24,3 +128,10 @@ impl Rules {
 		Ok(rules)
 	}
 }
+
+/// Print the used rules in the format of the rules file
+pub fn dump(args: &Args) -> Result<(), Box<dyn Error>> {
+	let rules = Rules::from_args(args)?;
+	println!("{}", serde_json::to_string_pretty(&rules)?);
+	Ok(())
+}
@@ -0,0 +1,115 @@
+use std::{
+	env,
+	error::Error,
+	net::TcpListener,
+	path::{Path, PathBuf},
+	process::Stdio,
+	time::Duration,
+};
+use tokio::{
+	process::{Child, Command},
+	time::{sleep, Instant},
+};
+
+use crate::{client::Client, Args};
+
+/// Name of the server jar in the LanguageTool download
+const JAR_NAME: &str = "languagetool-server.jar";
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// LanguageTool server started by `typst-lt`, stopped when dropped
+pub struct Server {
+	child: Child,
+}
+
+impl Server {
+	/// Start the server on `port` and point `host` and `port` of `args` to it
+	pub async fn spawn(args: &mut Args, port: u16) -> Result<Self, Box<dyn Error>> {
+		let jar = jar(args.server_jar.as_deref())?;
+		let java = env::var_os("JAVA_HOME")
+			.map(|home| PathBuf::from(home).join("bin").join("java"))
+			.unwrap_or_else(|| PathBuf::from("java"));
+		let child = Command::new(&java)
+			.arg("-cp")
+			.arg(&jar)
+			.arg("org.languagetool.server.HTTPServer")
+			.arg("--port")
+			.arg(port.to_string())
+			.stdin(Stdio::null())
+			.stdout(Stdio::null())
+			.stderr(Stdio::null())
+			.kill_on_drop(true)
+			.spawn()
+			.map_err(|err| format!("Failed to start {}: {}", java.display(), err))?;
+
+		args.host = String::from("http://127.0.0.1");
+		args.port = port.to_string();
+		let mut server = Self { child };
+		server.ready(&Client::new(args)).await?;
+		Ok(server)
+	}
+
+	/// Wait until the server answers requests
+	async fn ready(&mut self, client: &Client) -> Result<(), Box<dyn Error>> {
+		let start = Instant::now();
+		loop {
+			if client.languages().await.is_ok() {
+				return Ok(());
+			}
+			if let Some(status) = self.child.try_wait()? {
+				return Err(format!("LanguageTool server exited with {}", status).into());
+			}
+			if start.elapsed() > STARTUP_TIMEOUT {
+				return Err(format!(
+					"LanguageTool server did not start within {} seconds",
+					STARTUP_TIMEOUT.as_secs()
+				)
+				.into());
+			}
+			sleep(Duration::from_millis(250)).await;
+		}
+	}
+}
+
+/// Run a server on `port` until Ctrl+C is pressed
+pub async fn run(mut args: Args) -> Result<(), Box<dyn Error>> {
+	let port = args
+		.port
+		.parse()
+		.map_err(|_| format!("Invalid port {}", args.port))?;
+	let mut server = Server::spawn(&mut args, port).await?;
+	println!(
+		"LanguageTool server running at {}:{}, stop with Ctrl+C",
+		args.host, args.port
+	);
+	tokio::select! {
+		status = server.child.wait() => Err(format!("LanguageTool server exited with {}", status?).into()),
+		result = tokio::signal::ctrl_c() => Ok(result?),
+	}
+}
+
+pub fn free_port() -> Result<u16, Box<dyn Error>> {
+	Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+/// The server jar, `path` can also be the folder of the LanguageTool download
+fn jar(path: Option<&Path>) -> Result<PathBuf, Box<dyn Error>> {
+	let path = match path {
+		Some(path) => path,
+		None => {
+			return Err(
+				"No LanguageTool server found, set `--server-jar`, `server-jar` in the config file or `LANGUAGETOOL_JAR`"
+					.into(),
+			)
+		},
+	};
+	let jar = if path.is_dir() {
+		path.join(JAR_NAME)
+	} else {
+		path.to_owned()
+	};
+	if !jar.is_file() {
+		return Err(format!("LanguageTool server {} not found", jar.display()).into());
+	}
+	Ok(jar)
+}
@@ -0,0 +1,103 @@
+use clap::ValueEnum;
+use languagetool_rust::check::Match;
+use serde::Deserialize;
+
+use crate::Args;
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+	Info,
+	Warning,
+	Error,
+}
+
+impl Severity {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Info => "info",
+			Self::Warning => "warning",
+			Self::Error => "error",
+		}
+	}
+
+	/// Level of a SARIF result
+	pub fn sarif_level(self) -> &'static str {
+		match self {
+			Self::Info => "note",
+			Self::Warning => "warning",
+			Self::Error => "error",
+		}
+	}
+}
+
+/// Lowest severity that fails `check`
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailOn {
+	Info,
+	Warning,
+	Error,
+	Never,
+}
+
+impl FailOn {
+	fn includes(self, severity: Severity) -> bool {
+		match self {
+			Self::Info => true,
+			Self::Warning => severity >= Severity::Warning,
+			Self::Error => severity == Severity::Error,
+			Self::Never => false,
+		}
+	}
+}
+
+/// Parse `CATEGORY=SEVERITY` for `--severity`
+pub fn parse(value: &str) -> Result<(String, Severity), String> {
+	let (category, severity) = value
+		.split_once('=')
+		.ok_or_else(|| format!("Expected CATEGORY=SEVERITY, found {}", value))?;
+	let severity = Severity::from_str(severity, true)?;
+	Ok((category.to_owned(), severity))
+}
+
+/// Severity of the category of `info`, later mappings take precedence
+pub fn severity(args: &Args, info: &Match) -> Severity {
+	args.severities
+		.iter()
+		.rev()
+		.find(|(category, _)| *category == info.rule.category.id)
+		.map_or(Severity::Warning, |&(_, severity)| severity)
+}
+
+/// Number of matches failing `check`
+#[derive(Default)]
+pub struct Summary {
+	errors: usize,
+	warnings: usize,
+}
+
+impl Summary {
+	pub fn add(&mut self, args: &Args, matches: &[Match]) {
+		for info in matches {
+			let severity = severity(args, info);
+			if !args.fail_on.includes(severity) {
+				continue;
+			}
+			if severity == Severity::Error {
+				self.errors += 1;
+			} else {
+				self.warnings += 1;
+			}
+		}
+	}
+
+	/// Errors always fail, other matches only above `--max-warnings`
+	pub fn failed(&self, args: &Args) -> bool {
+		self.errors > 0 || self.warnings > args.max_warnings
+	}
+
+	pub fn print(&self) {
+		eprintln!("{} errors and {} warnings", self.errors, self.warnings);
+	}
+}
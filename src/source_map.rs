@@ -0,0 +1,160 @@
+use std::ops::Range;
+
+/// Maps char offsets in the checked text of a chunk to byte offsets in the source
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+	segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug)]
+struct Segment {
+	/// Char offset in the checked text
+	checked: usize,
+	/// Length in chars
+	length: usize,
+	/// Byte range in the source
+	source: Range<usize>,
+	/// Checked as text and not as markup
+	text: bool,
+}
+
+impl SourceMap {
+	/// `source` at byte offset `start` is checked at char offset `checked`
+	pub fn push(&mut self, checked: usize, source: &str, start: usize, text: bool) {
+		let length = source.chars().count();
+		if length == 0 {
+			return;
+		}
+		if let Some(last) = self.segments.last_mut() {
+			if last.text == text
+				&& last.checked + last.length == checked
+				&& last.source.end == start
+			{
+				last.length += length;
+				last.source.end += source.len();
+				return;
+			}
+		}
+		self.segments.push(Segment {
+			checked,
+			length,
+			source: start..start + source.len(),
+			text,
+		});
+	}
+
+	/// Byte offset in `source` of the char at `offset` in the checked text
+	pub fn source(&self, offset: usize, source: &str) -> usize {
+		let index = self
+			.segments
+			.partition_point(|segment| segment.checked + segment.length <= offset);
+		let segment = match self.segments.get(index) {
+			Some(segment) => segment,
+			None => return self.segments.last().map_or(0, |segment| segment.source.end),
+		};
+		let content = &source[segment.source.clone()];
+		let inner = offset.saturating_sub(segment.checked);
+		segment.source.start
+			+ content
+				.char_indices()
+				.nth(inner)
+				.map_or(content.len(), |(index, _)| index)
+	}
+
+	/// Byte range in `source` of `length` chars at `offset` in the checked text
+	pub fn range(&self, offset: usize, length: usize, source: &str) -> Range<usize> {
+		let start = self.source(offset, source);
+		if length == 0 {
+			return start..start;
+		}
+		let last = self.source(offset + length - 1, source);
+		let end = last + source[last..].chars().next().map_or(0, char::len_utf8);
+		start..end.max(start)
+	}
+
+	/// Whether `length` chars at `offset` in the checked text are text and not markup
+	pub fn is_text(&self, offset: usize, length: usize) -> bool {
+		let range = offset..offset + length;
+		self.segments
+			.iter()
+			.filter(|segment| {
+				segment.checked < range.end && range.start < segment.checked + segment.length
+			})
+			.all(|segment| segment.text)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SOURCE: &str = "Grüße #emph[schön] ok";
+
+	/// Map of `SOURCE` as converted, with the call as markup
+	fn map() -> SourceMap {
+		let mut map = SourceMap::default();
+		let (mut checked, mut start) = (0, 0);
+		for (part, text) in [
+			("Grüße ", true),
+			("#emph[", false),
+			("schön", true),
+			("]", false),
+			(" ok", true),
+		] {
+			map.push(checked, part, start, text);
+			checked += part.chars().count();
+			start += part.len();
+		}
+		map
+	}
+
+	fn slice(range: Range<usize>) -> &'static str {
+		&SOURCE[range]
+	}
+
+	#[test]
+	fn multibyte() {
+		let map = map();
+		assert_eq!(map.source(2, SOURCE), "Gr".len());
+		assert_eq!(slice(map.range(3, 2, SOURCE)), "ße");
+		assert_eq!(slice(map.range(12, 5, SOURCE)), "schön");
+		assert_eq!(slice(map.range(15, 2, SOURCE)), "ön");
+	}
+
+	#[test]
+	fn segment_edges() {
+		let map = map();
+		assert_eq!(slice(map.range(5, 1, SOURCE)), " ");
+		assert_eq!(map.source(6, SOURCE), "Grüße ".len());
+		assert_eq!(slice(map.range(16, 1, SOURCE)), "n");
+		assert_eq!(slice(map.range(17, 3, SOURCE)), "] o");
+		assert_eq!(slice(map.range(4, 4, SOURCE)), "e #e");
+	}
+
+	#[test]
+	fn empty_and_past_end() {
+		let map = map();
+		assert_eq!(map.range(12, 0, SOURCE), 14..14);
+		assert_eq!(map.source(100, SOURCE), SOURCE.len());
+		assert_eq!(SourceMap::default().range(3, 2, ""), 0..0);
+	}
+
+	#[test]
+	fn merged_segments() {
+		let mut map = SourceMap::default();
+		map.push(0, "ä", 0, true);
+		map.push(1, "", 2, true);
+		map.push(1, "öü", 2, true);
+		assert_eq!(map.segments.len(), 1);
+		assert_eq!(map.range(1, 2, "äöü"), 2..6);
+	}
+
+	#[test]
+	fn text() {
+		let map = map();
+		assert!(map.is_text(12, 5));
+		assert!(map.is_text(18, 3));
+		assert!(!map.is_text(11, 2));
+		assert!(!map.is_text(6, 6));
+	}
+}
@@ -0,0 +1,104 @@
+use languagetool_rust::check::Match;
+
+use crate::convert::Chunk;
+
+/// Regions disabled with comments, as byte offsets into the source
+///
+/// - `// lt-disable-next-line [RULE_ID ...]`
+/// - `/* lt-disable [RULE_ID ...] */ ... /* lt-enable */`
+/// - `// lt-disable-file [RULE_ID ...]`
+///
+/// Without rule ids all rules are disabled.
+pub struct Suppressions {
+	regions: Vec<Region>,
+	open: Vec<usize>,
+	next_line: Option<NextLine>,
+}
+
+struct Region {
+	start: usize,
+	end: usize,
+	rules: Vec<String>,
+}
+
+struct NextLine {
+	start: Option<usize>,
+	rules: Vec<String>,
+}
+
+impl Suppressions {
+	pub fn new() -> Self {
+		Self {
+			regions: Vec::new(),
+			open: Vec::new(),
+			next_line: None,
+		}
+	}
+
+	/// Comment ending at `position`
+	pub fn comment(&mut self, comment: &str, position: usize) {
+		let content = match comment.strip_prefix("//") {
+			Some(content) => content,
+			None => comment.trim_start_matches("/*").trim_end_matches("*/"),
+		};
+		let mut words = content.split_whitespace();
+		let directive = words.next();
+		let rules = words.map(String::from).collect();
+		match directive {
+			Some("lt-disable-next-line") => {
+				self.next_line = Some(NextLine { start: None, rules });
+			},
+			Some("lt-disable") => {
+				self.open.push(self.regions.len());
+				self.regions
+					.push(Region { start: position, end: usize::MAX, rules });
+			},
+			Some("lt-enable") => {
+				for index in self.open.drain(..) {
+					self.regions[index].end = position;
+				}
+			},
+			Some("lt-disable-file") => {
+				self.regions
+					.push(Region { start: 0, end: usize::MAX, rules });
+			},
+			_ => {},
+		}
+	}
+
+	/// Newline at `position`
+	pub fn newline(&mut self, position: usize) {
+		let next_line = match &mut self.next_line {
+			Some(next_line) => next_line,
+			None => return,
+		};
+		match next_line.start {
+			None => next_line.start = Some(position + 1),
+			Some(start) => {
+				let rules = std::mem::take(&mut next_line.rules);
+				self.regions.push(Region { start, end: position, rules });
+				self.next_line = None;
+			},
+		}
+	}
+
+	pub fn finish(&mut self) {
+		if let Some(NextLine { start: Some(start), rules }) = self.next_line.take() {
+			self.regions.push(Region { start, end: usize::MAX, rules });
+		}
+	}
+
+	/// Remove matches starting in a disabled region
+	pub fn filter(&self, matches: &mut Vec<Match>, chunk: &Chunk, source: &str) {
+		if self.regions.is_empty() {
+			return;
+		}
+		matches.retain(|info| {
+			let position = chunk.map.source(info.offset, source);
+			!self.regions.iter().any(|region| {
+				(region.start..region.end).contains(&position)
+					&& (region.rules.is_empty() || region.rules.contains(&info.rule.id))
+			})
+		});
+	}
+}